@@ -1,7 +1,18 @@
-use bren::renderer::{model::Model, viewport::Viewport, Renderer};
-use std::{thread::sleep, time::Duration};
+use bren::renderer::{
+    model::Model,
+    terminal::{quit_requested, TerminalGuard, TerminalOptions},
+    viewport::Viewport,
+    Renderer,
+};
+use std::time::Duration;
 
 fn main() {
+    let _terminal = TerminalGuard::with_options(TerminalOptions {
+        raw_mode: true,
+        ..TerminalOptions::default()
+    })
+    .expect("Terminal should support raw mode");
+
     let mut renderer = Renderer::new(Viewport::new());
 
     let mut cube =
@@ -10,14 +21,12 @@ fn main() {
     cube.translate(0.0, 0.0, -5.0);
 
     let mut i = 0.0;
-    loop {
+    while !quit_requested(Duration::from_millis(1000 / 30)).unwrap() {
         i = if i < 359.0 { i + 1.0 } else { 0.0 };
 
         renderer.clear();
         cube.rotate(0.0, i, 0.0);
         renderer.draw_object(&cube);
         renderer.render();
-
-        sleep(Duration::from_millis(1000 / 30));
     }
 }
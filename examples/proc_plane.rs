@@ -1,15 +1,27 @@
-use bren::renderer::{model::Model, viewport::Viewport, Renderer};
-use std::{thread::sleep, time::Duration};
+use bren::renderer::{
+    model::Model,
+    terminal::{quit_requested, TerminalGuard, TerminalOptions},
+    viewport::Viewport,
+    CullMode, Renderer,
+};
+use std::time::Duration;
 
 fn main() {
+    let _terminal = TerminalGuard::with_options(TerminalOptions {
+        raw_mode: true,
+        ..TerminalOptions::default()
+    })
+    .expect("Terminal should support raw mode");
+
     let mut renderer = Renderer::new_wireframe(Viewport::new());
 
     let mut plane = Model::new_plane(16, 5.0);
+    plane.cull_mode = Some(CullMode::None);
 
     plane.translate(0.0, 0.0, -10.0);
 
     let mut i = 0.0;
-    loop {
+    while !quit_requested(Duration::from_millis(1000 / 30)).unwrap() {
         for vertex in &mut plane.vertex_buffer {
             vertex.position.z = ((std::f32::consts::PI
                 * (vertex.position.x + (i / std::f32::consts::PI)))
@@ -24,7 +36,5 @@ fn main() {
         renderer.clear();
         renderer.draw_object(&plane);
         renderer.render();
-
-        sleep(Duration::from_millis(1000 / 30));
     }
 }
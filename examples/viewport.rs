@@ -1,6 +1,18 @@
-use bren::renderer::{model::Model, viewport::Viewport, Renderer};
+use bren::renderer::{
+    model::Model,
+    terminal::{quit_requested, TerminalGuard, TerminalOptions},
+    viewport::Viewport,
+    Renderer,
+};
+use std::time::Duration;
 
 fn main() {
+    let _terminal = TerminalGuard::with_options(TerminalOptions {
+        raw_mode: true,
+        ..TerminalOptions::default()
+    })
+    .expect("Terminal should support raw mode");
+
     let screen_size = Viewport::screen_size().unwrap();
     let viewport = Viewport::with_size_and_pos(
         screen_size.0 / 2,
@@ -20,5 +32,5 @@ fn main() {
     renderer.draw_object(&cube);
     renderer.render();
 
-    loop {}
+    while !quit_requested(Duration::from_secs(1)).unwrap() {}
 }
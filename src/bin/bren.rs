@@ -0,0 +1,352 @@
+//! Interactive model viewer.
+//!
+//! Opens any model file the library can load and shows it in the terminal. Drag with the left
+//! mouse button or use the arrow keys to orbit, drag with the right button or use WASD to pan,
+//! and scroll or press `+` and `-` to zoom.
+
+use std::{
+    env,
+    error::Error,
+    f32::consts::FRAC_PI_2,
+    io::{self, stdout, Write},
+    path::Path,
+    process,
+    time::{Duration, Instant},
+};
+
+use bren::renderer::{
+    camera::Camera,
+    model::{Aabb, Model},
+    terminal::{TerminalGuard, TerminalOptions},
+    viewport::{TextStyle, Viewport},
+    Color, Gizmos, RenderMode, Renderer,
+};
+use crossterm::{
+    event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
+    execute, terminal,
+};
+use nalgebra::{Isometry3, Point3, Vector3};
+
+const USAGE: &str = "usage: bren <model file>";
+
+const HELP: &str = "drag/arrows orbit  right drag/wasd pan  scroll/+- zoom  m mode  l light  \
+                    g grid  x axes  b box  n normals  r reset  q quit";
+
+/// Longest time spent on a frame, including waiting for input.
+const FRAME_TIME: Duration = Duration::from_millis(1000 / 60);
+
+/// Degrees turned by each arrow key press.
+const ORBIT_STEP: f32 = 10.0;
+
+/// Degrees turned by dragging the mouse over one cell. Cells are about twice as tall as wide.
+const DRAG_DEGREES: (f32, f32) = (3.0, 6.0);
+
+/// Distance moved by each pan key press or cell dragged over, relative to the distance from the
+/// target.
+const PAN_STEP: f32 = 0.05;
+
+/// Change in distance by each zoom key press or scroll.
+const ZOOM_STEP: f32 = 1.2;
+
+const RENDER_MODES: [RenderMode; 6] = [
+    RenderMode::Solid,
+    RenderMode::Wireframe,
+    RenderMode::SolidWireframe,
+    RenderMode::HiddenLine,
+    RenderMode::Points,
+    RenderMode::PointCloud,
+];
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let (Some(path), None) = (args.next(), args.next()) else {
+        eprintln!("{USAGE}");
+        process::exit(2);
+    };
+    let models = match load(&path) {
+        Ok(models) => models,
+        Err(error) => {
+            eprintln!("bren: could not load {path}: {error}");
+            process::exit(1);
+        }
+    };
+    if let Err(error) = run(models) {
+        eprintln!("bren: {error}");
+        process::exit(1);
+    }
+}
+
+fn load(path: &str) -> Result<Vec<Model>, Box<dyn Error>> {
+    #[cfg(feature = "gltf")]
+    {
+        let extension = Path::new(path).extension().and_then(|e| e.to_str());
+        if extension
+            .is_some_and(|e| e.eq_ignore_ascii_case("gltf") || e.eq_ignore_ascii_case("glb"))
+        {
+            return Ok(bren::renderer::scene::Scene::load_gltf(path)?.models());
+        }
+    }
+
+    let name = Path::new(path)
+        .file_name()
+        .map_or(path.into(), |n| n.to_string_lossy());
+    let mut shown = false;
+    let model = Model::load_from_file_with_progress(path, |progress| {
+        eprint!("\rloading {name}: {:3.0}%", progress.fraction() * 100.0);
+        shown = true;
+    });
+    if shown {
+        eprintln!();
+    }
+    Ok(vec![model?])
+}
+
+fn run(models: Vec<Model>) -> io::Result<()> {
+    let _terminal = TerminalGuard::with_options(TerminalOptions {
+        raw_mode: true,
+        mouse_capture: true,
+    })?;
+    let mut viewer = ModelViewer::new(models);
+    loop {
+        let frame_end = Instant::now() + FRAME_TIME;
+        viewer.draw();
+        while event::poll(frame_end.saturating_duration_since(Instant::now()))? {
+            if !viewer.handle_event(event::read()?)? {
+                return Ok(());
+            }
+            if Instant::now() >= frame_end {
+                break;
+            }
+        }
+    }
+}
+
+/// A camera that circles around a target point, always looking at it.
+#[derive(Clone, Copy)]
+struct Orbit {
+    target: Point3<f32>,
+    distance: f32,
+    /// Angle around the y-axis in degrees.
+    yaw: f32,
+    /// Angle above the ground in degrees.
+    pitch: f32,
+}
+
+impl Orbit {
+    fn eye(&self) -> Point3<f32> {
+        let (yaw, pitch) = (self.yaw.to_radians(), self.pitch.to_radians());
+        let direction = Vector3::new(
+            pitch.cos() * yaw.sin(),
+            pitch.sin(),
+            pitch.cos() * yaw.cos(),
+        );
+        self.target + direction * self.distance
+    }
+
+    fn view_matrix(&self) -> Isometry3<f32> {
+        Isometry3::look_at_rh(&self.eye(), &self.target, &Vector3::y())
+    }
+
+    fn rotate(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = (self.yaw + yaw) % 360.0;
+        self.pitch = (self.pitch + pitch).clamp(-89.0, 89.0);
+    }
+
+    /// Moves the target across the screen, by steps relative to the distance.
+    fn pan(&mut self, right: f32, up: f32) {
+        let rotation = self.view_matrix().rotation.inverse();
+        let offset = rotation * Vector3::new(right, up, 0.0) * self.distance * PAN_STEP;
+        self.target += offset;
+    }
+
+    fn zoom(&mut self, factor: f32, min_distance: f32) {
+        self.distance = (self.distance * factor).max(min_distance);
+    }
+}
+
+struct ModelViewer {
+    renderer: Renderer,
+    models: Vec<Model>,
+    /// Radius of a sphere that contains every model.
+    radius: f32,
+    home: Orbit,
+    orbit: Orbit,
+    render_mode: usize,
+    lighting: bool,
+    gizmos: Gizmos,
+    /// Last cell the mouse was dragged over, and the button held.
+    drag: Option<(u16, u16, MouseButton)>,
+    frames: u32,
+    fps_since: Instant,
+    fps: f32,
+}
+
+impl ModelViewer {
+    fn new(models: Vec<Model>) -> ModelViewer {
+        let bounds = models
+            .iter()
+            .filter_map(|model| {
+                let aabb = model.bounding_box()?;
+                let matrix = model.model_matrix();
+                let [a, b] = [aabb.min, aabb.max].map(|corner| matrix * corner);
+                Some([a.inf(&b), a.sup(&b)])
+            })
+            .reduce(|a, b| [a[0].inf(&b[0]), a[1].sup(&b[1])])
+            .map(|[min, max]| Aabb { min, max });
+        let (center, radius, ground) = bounds.map_or((Point3::origin(), 1.0, 0.0), |aabb| {
+            let radius = aabb.size().norm() / 2.0;
+            (aabb.center(), radius.max(f32::EPSILON), aabb.min.y)
+        });
+
+        let home = Orbit {
+            target: center,
+            distance: radius * 2.5,
+            yaw: 30.0,
+            pitch: 20.0,
+        };
+        let gizmos = Gizmos {
+            axis_length: radius,
+            grid_spacing: 10f32.powf((radius / 2.0).log10().round()),
+            grid_height: ground,
+            ..Gizmos::default()
+        };
+
+        ModelViewer {
+            renderer: Renderer::new(Viewport::new()),
+            models,
+            radius,
+            home,
+            orbit: home,
+            render_mode: 0,
+            lighting: true,
+            gizmos,
+            drag: None,
+            frames: 0,
+            fps_since: Instant::now(),
+            fps: 0.0,
+        }
+    }
+
+    fn draw(&mut self) {
+        let (width, height) = self.renderer.viewport.size();
+        let far = (self.orbit.distance + self.radius) * 20.0;
+        let mut camera = Camera::new(
+            width as f32 / height as f32,
+            FRAC_PI_2,
+            self.orbit.distance * 0.01,
+            far,
+        );
+        camera.view_matrix = self.orbit.view_matrix();
+        self.renderer.set_camera(camera);
+        self.renderer
+            .set_render_mode(RENDER_MODES[self.render_mode]);
+        self.renderer.set_lighting(self.lighting);
+        self.renderer.set_gizmos(self.gizmos);
+
+        self.renderer.clear();
+        for model in &self.models {
+            self.renderer.draw_object(model);
+        }
+        self.draw_overlay();
+        self.renderer.render();
+
+        self.frames += 1;
+        let elapsed = self.fps_since.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            self.fps = self.frames as f32 / elapsed.as_secs_f32();
+            self.frames = 0;
+            self.fps_since = Instant::now();
+        }
+    }
+
+    fn draw_overlay(&mut self) {
+        let style = TextStyle {
+            fg: Some(Color::BLACK),
+            bg: Some(Color::WHITE),
+            ..TextStyle::default()
+        };
+        let status = format!(
+            " {:.0} fps | {:?} | lighting {} ",
+            self.fps,
+            RENDER_MODES[self.render_mode],
+            if self.lighting { "on" } else { "off" }
+        );
+        let rows = self.renderer.viewport.size_in_cells().1;
+        let viewport = &mut self.renderer.viewport;
+        viewport.draw_text(0, 0, &status, style);
+        viewport.draw_text(0, rows.saturating_sub(1), HELP, TextStyle::default());
+    }
+
+    /// Applies an input event, returning whether the viewer should keep running.
+    fn handle_event(&mut self, event: Event) -> io::Result<bool> {
+        match event {
+            Event::Key(key) if key.kind != KeyEventKind::Release => return Ok(self.handle_key(key)),
+            Event::Mouse(mouse) => self.handle_mouse(mouse),
+            Event::Resize(_, _) => {
+                self.renderer.viewport = Viewport::new();
+                execute!(stdout(), terminal::Clear(terminal::ClearType::All))?;
+                stdout().flush()?;
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let min_distance = self.radius * 0.1;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Left => self.orbit.rotate(-ORBIT_STEP, 0.0),
+            KeyCode::Right => self.orbit.rotate(ORBIT_STEP, 0.0),
+            KeyCode::Up => self.orbit.rotate(0.0, ORBIT_STEP),
+            KeyCode::Down => self.orbit.rotate(0.0, -ORBIT_STEP),
+            KeyCode::Char('a') => self.orbit.pan(-1.0, 0.0),
+            KeyCode::Char('d') => self.orbit.pan(1.0, 0.0),
+            KeyCode::Char('w') => self.orbit.pan(0.0, 1.0),
+            KeyCode::Char('s') => self.orbit.pan(0.0, -1.0),
+            KeyCode::Char('+') | KeyCode::Char('=') => {
+                self.orbit.zoom(1.0 / ZOOM_STEP, min_distance)
+            }
+            KeyCode::Char('-') => self.orbit.zoom(ZOOM_STEP, min_distance),
+            KeyCode::Char('m') => self.render_mode = (self.render_mode + 1) % RENDER_MODES.len(),
+            KeyCode::Char('l') => self.lighting = !self.lighting,
+            KeyCode::Char('g') => self.gizmos.grid = !self.gizmos.grid,
+            KeyCode::Char('x') => self.gizmos.axes = !self.gizmos.axes,
+            KeyCode::Char('b') => self.gizmos.bounding_boxes = !self.gizmos.bounding_boxes,
+            KeyCode::Char('n') => self.gizmos.normals = !self.gizmos.normals,
+            KeyCode::Char('r') => self.orbit = self.home,
+            _ => {}
+        }
+        true
+    }
+
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let min_distance = self.radius * 0.1;
+        match mouse.kind {
+            MouseEventKind::Down(button) => self.drag = Some((mouse.column, mouse.row, button)),
+            MouseEventKind::Up(_) => self.drag = None,
+            MouseEventKind::Drag(button) => {
+                if let Some((column, row, held)) = self.drag.filter(|drag| drag.2 == button) {
+                    let dx = mouse.column as f32 - column as f32;
+                    let dy = mouse.row as f32 - row as f32;
+                    match button {
+                        MouseButton::Left => {
+                            self.orbit.rotate(-dx * DRAG_DEGREES.0, dy * DRAG_DEGREES.1)
+                        }
+                        _ => self.orbit.pan(-dx, dy * 2.0),
+                    }
+                    self.drag = Some((mouse.column, mouse.row, held));
+                } else {
+                    self.drag = Some((mouse.column, mouse.row, button));
+                }
+            }
+            MouseEventKind::ScrollUp => self.orbit.zoom(1.0 / ZOOM_STEP, min_distance),
+            MouseEventKind::ScrollDown => self.orbit.zoom(ZOOM_STEP, min_distance),
+            _ => {}
+        }
+    }
+}
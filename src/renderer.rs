@@ -1,37 +1,286 @@
+mod bezier;
 pub mod camera;
+mod canvas;
+pub mod color;
+pub mod dither;
+mod gizmos;
+mod lines;
+pub mod material;
 pub mod model;
+#[cfg(feature = "gltf")]
+pub mod scene;
+pub mod terminal;
 pub mod viewport;
 
 use crate::renderer::model::Model;
-use nalgebra::{Matrix4, Point2, Point3, Vector3};
-use viewport::Viewport;
+pub use color::Color;
+use dither::Dithering;
+pub use gizmos::Gizmos;
+use material::{BlendMode, Material};
+use nalgebra::{Isometry3, Matrix4, Point2, Point3, Vector3, Vector4};
+use std::ops::Range;
+use viewport::{TextStyle, Viewport};
 
 use self::camera::Camera;
 
-#[derive(Clone, Copy, Debug)]
-pub struct Color(u8, u8, u8);
-
 /// A single point in 3D-space.
 #[derive(Clone, Debug)]
 pub struct Vertex {
     pub position: Point3<f32>,
+    /// Colour of the model at this vertex. Vertices without one are white.
+    pub color: Option<Color>,
+    /// Surface normal at this vertex, pointing out of the model, used for smooth lighting. Faces
+    /// are lit by their own normal unless all of their vertices have one.
+    pub normal: Option<Vector3<f32>>,
+    /// Texture coordinates at this vertex. Kept when loading and saving models, the renderer does
+    /// not use them.
+    pub uv: Option<Point2<f32>>,
+    /// Opacity of the model at this vertex, between 0 and 1. Vertices without one are opaque.
+    /// Faces and points that are not fully opaque are blended like a transparent [`Material`].
+    pub alpha: Option<f32>,
 }
 
 impl Vertex {
+    /// Constructs a vertex without a colour, normal or texture coordinates.
+    pub fn new(position: Point3<f32>) -> Vertex {
+        Vertex {
+            position,
+            color: None,
+            normal: None,
+            uv: None,
+            alpha: None,
+        }
+    }
+
     /// Applies model view projection and returns screen coordinates.
     fn project(&mut self, mvp_matrix: Matrix4<f32>, view_width: f32, view_height: f32) {
         self.position = mvp_matrix.transform_point(&self.position);
+        self.map_to_screen(view_width, view_height);
+    }
+
+    /// Turns normalized device coordinates into screen coordinates.
+    fn map_to_screen(&mut self, view_width: f32, view_height: f32) {
         self.position.x = self.position.x * view_width + view_width / 2.0;
         self.position.y = self.position.y * view_height + view_height / 2.0;
     }
 }
 
+/// Which faces of a model are discarded before rasterisation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CullMode {
+    /// Every face is drawn.
+    None,
+    /// Faces facing away from the camera are skipped.
+    #[default]
+    Back,
+    /// Faces facing the camera are skipped.
+    Front,
+}
+
+/// Order in which the vertices of a face appear on the screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Winding {
+    /// Vertices go clockwise.
+    Clockwise,
+    /// Vertices go counter-clockwise.
+    #[default]
+    CounterClockwise,
+}
+
+impl Winding {
+    /// Winding of a screen-space triangle with the given signed area, as returned by
+    /// `edge_function`. Degenerate triangles are treated as counter-clockwise.
+    fn from_area(area: f32) -> Winding {
+        if area < 0.0 {
+            Winding::Clockwise
+        } else {
+            Winding::CounterClockwise
+        }
+    }
+}
+
+impl CullMode {
+    /// Returns true if a face with `winding` on the screen should be skipped, when faces wound
+    /// like `front_face` are considered to face the camera.
+    fn culls(self, winding: Winding, front_face: Winding) -> bool {
+        match self {
+            CullMode::None => false,
+            CullMode::Back => winding != front_face,
+            CullMode::Front => winding == front_face,
+        }
+    }
+}
+
 /// A face of a 3D-object.
 ///
-/// Contains the indexes of the vertices that form the face.
-#[derive(Debug)]
+/// Contains the indexes of the vertices that form the face. Indexes start at 0, and are checked
+/// against the vertex buffer when the face is added to a model with [`Model::from_buffers`].
+/// Faces pushed to the buffers afterwards with indexes out of range are not drawn.
+#[derive(Clone, Debug)]
 pub struct Face {
     indexes: (usize, usize, usize),
+    /// Colour of the whole face, taking precedence over the colours of its vertices.
+    pub color: Option<Color>,
+    /// Opacity of the whole face, taking precedence over the opacity of its vertices.
+    pub alpha: Option<f32>,
+}
+
+impl Face {
+    /// Constructs a face from the indexes of its vertices, counter-clockwise when seen from the
+    /// front.
+    pub fn new(a: usize, b: usize, c: usize) -> Face {
+        Face {
+            indexes: (a, b, c),
+            color: None,
+            alpha: None,
+        }
+    }
+
+    /// Indexes of the vertices that form the face.
+    pub fn indexes(&self) -> (usize, usize, usize) {
+        self.indexes
+    }
+
+    /// The vertices of the face, or `None` if any of its indexes is out of range.
+    fn vertices<'a>(&self, vertex_buffer: &'a [Vertex]) -> Option<[&'a Vertex; 3]> {
+        let (a, b, c) = self.indexes;
+        Some([
+            vertex_buffer.get(a)?,
+            vertex_buffer.get(b)?,
+            vertex_buffer.get(c)?,
+        ])
+    }
+}
+
+/// How vertex colours are applied across the faces of a model.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Shading {
+    /// Each face gets the average colour of its vertices.
+    Flat,
+    /// Vertex colours blend smoothly across each face.
+    #[default]
+    Smooth,
+}
+
+/// How the faces of a model are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Faces are filled and shaded.
+    #[default]
+    Solid,
+    /// Every edge of every visible face is drawn, including edges hidden behind other faces.
+    Wireframe,
+    /// Only the vertices of visible faces are drawn.
+    Points,
+    /// Faces are filled and shaded, with their edges drawn on top.
+    SolidWireframe,
+    /// Edges are drawn, except for the parts hidden behind the model's own faces or earlier
+    /// draws.
+    HiddenLine,
+    /// Every vertex is drawn as a dot in its own colour, whether it belongs to a face or not. See
+    /// [`PointStyle`]. Models without faces are always drawn like this.
+    PointCloud,
+}
+
+/// Colour and thickness used for drawing lines and points.
+#[derive(Clone, Copy, Debug)]
+pub struct LineStyle {
+    /// Colour of the line.
+    pub color: Color,
+    /// Width of the line in dots.
+    pub thickness: u16,
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        LineStyle {
+            color: Color::WHITE,
+            thickness: 1,
+        }
+    }
+}
+
+/// Fading of points with their distance from the camera, towards the clear colour or black.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthFade {
+    /// Distance at which points start to fade.
+    pub near: f32,
+    /// Distance at which points have faded completely. Points beyond it are not drawn.
+    pub far: f32,
+}
+
+impl DepthFade {
+    /// How far a point at the distance has faded, between 0 and 1.
+    fn amount(&self, distance: f32) -> f32 {
+        if self.far <= self.near {
+            return if distance < self.far { 0.0 } else { 1.0 };
+        }
+        ((distance - self.near) / (self.far - self.near)).clamp(0.0, 1.0)
+    }
+}
+
+/// How the vertices of a model are drawn in [`RenderMode::PointCloud`].
+#[derive(Clone, Copy, Debug)]
+pub struct PointStyle {
+    /// Colour of vertices without a colour of their own.
+    pub color: Color,
+    /// Width of each point in dots.
+    pub size: u16,
+    /// Fades distant points, or keeps all points at full brightness with `None`.
+    pub fade: Option<DepthFade>,
+}
+
+impl Default for PointStyle {
+    fn default() -> Self {
+        PointStyle {
+            color: Color::WHITE,
+            size: 1,
+            fade: None,
+        }
+    }
+}
+
+/// Anti-aliasing by rendering at a higher resolution and downsampling the result.
+///
+/// Every dot of the viewport is rendered as `factor` by `factor` samples. A dot is lit when at
+/// least `coverage_threshold` of its samples are lit, and gets the average colour of those
+/// samples. Thresholds below 0.5 keep thin features visible, higher ones give cleaner edges.
+#[derive(Clone, Copy, Debug)]
+pub struct Supersampling {
+    /// Samples per dot along each axis.
+    pub factor: u8,
+    /// Fraction of lit samples needed to light a dot, between 0 and 1.
+    pub coverage_threshold: f32,
+}
+
+impl Default for Supersampling {
+    fn default() -> Self {
+        Supersampling {
+            factor: 2,
+            coverage_threshold: 0.25,
+        }
+    }
+}
+
+/// A triangle of a face that survived culling, projected to the screen with its vertices in
+/// counter-clockwise order. Faces cut by the near plane may leave two.
+struct ProjectedFace {
+    vertices: [Vertex; 3],
+    /// Shaded colour of each vertex.
+    colors: [Color; 3],
+    /// Opacity of each vertex, including the material's.
+    alphas: [f32; 3],
+}
+
+/// A transparent face, waiting to be blended after all opaque geometry.
+#[derive(Debug)]
+struct TransparentFace {
+    vertices: [Vertex; 3],
+    colors: [Color; 3],
+    alphas: [f32; 3],
+    blend_mode: BlendMode,
+    /// Average distance of the vertices from the camera, used to sort faces back to front.
+    depth: f32,
 }
 
 /// A struct used to render 3D-objects.
@@ -49,20 +298,42 @@ pub struct Renderer {
     /// The viewport that the renderer draws onto.
     pub viewport: Viewport,
     screen_buffer: Vec<Vec<Color>>,
+    /// Whether anything has been drawn to each pixel. Only covered pixels become dots, so black
+    /// can be drawn like any other colour.
+    coverage_buffer: Vec<Vec<bool>>,
+    /// Distance from the camera of the closest surface drawn to each pixel.
+    depth_buffer: Vec<Vec<f32>>,
     camera: Camera,
-    wireframe: bool,
+    render_mode: RenderMode,
+    line_style: LineStyle,
+    point_style: PointStyle,
+    cull_mode: CullMode,
+    front_face: Winding,
+    supersampling: Option<Supersampling>,
+    dithering: Option<Dithering>,
+    clear_color: Option<Color>,
+    lighting: bool,
+    transparent_faces: Vec<TransparentFace>,
+    gizmos: Gizmos,
 }
 
+/// Tolerance of the depth test for lines and points, relative to the distance from the camera.
+/// Keeps edges from being hidden by the faces they lie on.
+const LINE_DEPTH_BIAS: f32 = 0.01;
+
+/// Screen area, in samples, that each face of a model should cover at least. Models that are
+/// smaller on the screen are drawn with one of their levels of detail.
+const LOD_SAMPLES_PER_FACE: f32 = 2.0;
+
 impl Renderer {
     /// Constructs the renderer. [`Viewport`] must be passed to the constructor.
     pub fn new(viewport: Viewport) -> Renderer {
         let viewport_size = viewport.size();
 
-        Renderer {
-            screen_buffer: vec![
-                vec![Color(0, 0, 0); viewport_size.1 as usize];
-                viewport_size.0 as usize
-            ],
+        let mut renderer = Renderer {
+            screen_buffer: vec![],
+            coverage_buffer: vec![],
+            depth_buffer: vec![],
             viewport,
             camera: Camera::new(
                 viewport_size.0 as f32 / viewport_size.1 as f32,
@@ -70,148 +341,837 @@ impl Renderer {
                 1.0,
                 1000.0,
             ),
-            wireframe: false,
-        }
+            render_mode: RenderMode::default(),
+            line_style: LineStyle::default(),
+            point_style: PointStyle::default(),
+            cull_mode: CullMode::default(),
+            front_face: Winding::default(),
+            supersampling: None,
+            dithering: None,
+            clear_color: None,
+            lighting: true,
+            transparent_faces: vec![],
+            gizmos: Gizmos::default(),
+        };
+        renderer.clear();
+        renderer
     }
 
     /// Constructs a wireframe renderer. [`Viewport`] must be passed to the constructor.
+    ///
+    /// Same as calling [`new`] followed by [`set_render_mode`] with [`RenderMode::Wireframe`].
+    ///
+    /// [`new`]: #method.new
+    /// [`set_render_mode`]: #method.set_render_mode
     pub fn new_wireframe(viewport: Viewport) -> Renderer {
-        let viewport_size = viewport.size();
+        let mut renderer = Renderer::new(viewport);
+        renderer.render_mode = RenderMode::Wireframe;
+        renderer
+    }
 
-        Renderer {
-            screen_buffer: vec![
-                vec![Color(0, 0, 0); viewport_size.1 as usize];
-                viewport_size.0 as usize
-            ],
-            viewport,
-            camera: Camera::new(
-                viewport_size.0 as f32 / viewport_size.1 as f32,
-                std::f32::consts::FRAC_PI_2,
-                1.0,
-                1000.0,
-            ),
-            wireframe: true,
-        }
+    /// Sets how models are drawn. Defaults to [`RenderMode::Solid`]. Models can override this
+    /// with [`Model::render_mode`].
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) {
+        self.render_mode = render_mode;
+    }
+
+    /// Sets the colour and thickness of edges and points. Models can override this with
+    /// [`Model::line_style`].
+    pub fn set_line_style(&mut self, line_style: LineStyle) {
+        self.line_style = line_style;
+    }
+
+    /// Sets how point clouds are drawn, see [`RenderMode::PointCloud`]. Models can override this
+    /// with [`Model::point_style`].
+    pub fn set_point_style(&mut self, point_style: PointStyle) {
+        self.point_style = point_style;
+    }
+
+    /// Sets which faces are skipped when drawing. Defaults to [`CullMode::Back`]. Models can
+    /// override this with [`Model::cull_mode`].
+    pub fn set_cull_mode(&mut self, cull_mode: CullMode) {
+        self.cull_mode = cull_mode;
+    }
+
+    /// Sets the screen-space winding of faces that face the camera. Defaults to
+    /// [`Winding::CounterClockwise`].
+    pub fn set_front_face(&mut self, front_face: Winding) {
+        self.front_face = front_face;
+    }
+
+    /// Enables anti-aliasing by supersampling, or disables it with `None`. Disabled by default.
+    ///
+    /// This clears the screen buffer, as its resolution changes.
+    pub fn set_supersampling(&mut self, supersampling: Option<Supersampling>) {
+        self.supersampling = supersampling.filter(|supersampling| supersampling.factor > 1);
+        self.clear();
+    }
+
+    /// Sets how shading is turned into patterns of dots, or disables dithering with `None`.
+    /// Disabled by default.
+    pub fn set_dithering(&mut self, dithering: Option<Dithering>) {
+        self.dithering = dithering;
+    }
+
+    /// Sets the colour of the background, which the screen buffer is cleared to. With `None`, the
+    /// default, the terminal's own background colour shows through.
+    pub fn set_clear_color(&mut self, clear_color: Option<Color>) {
+        self.clear_color = clear_color;
+        self.clear();
+    }
+
+    /// Turns lighting on or off. Lit faces are shaded by how directly they face the camera, unlit
+    /// faces are drawn in their full colour. On by default.
+    pub fn set_lighting(&mut self, lighting: bool) {
+        self.lighting = lighting;
+    }
+
+    /// The camera through which models are drawn.
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// Replaces the camera, for example to move it around or to match a new viewport size. By
+    /// default the camera is at the origin, looking along the negative z-axis, with a vertical
+    /// field of view of 90 degrees.
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = camera;
     }
 
     /// Renders the screen buffer on the screen. Should be called after draw-calls. This will not
     /// erase the screen buffer, so you should call [`clear`] after.
     ///
+    /// Gizmos that belong to the world are drawn first, see [`set_gizmos`], then transparent
+    /// faces that have not been drawn yet, see [`draw_transparent`].
+    ///
     /// [`clear`]: #method.clear
+    /// [`set_gizmos`]: #method.set_gizmos
+    /// [`draw_transparent`]: #method.draw_transparent
     pub fn render(&mut self) {
-        self.viewport.draw_chars(&self.screen_buffer);
+        self.draw_world_gizmos();
+        self.draw_transparent();
+        if self.supersampling.is_none() && self.dithering.is_none() {
+            self.viewport
+                .draw_chars(&self.screen_buffer, &self.coverage_buffer, self.clear_color);
+            return;
+        }
+
+        let (mut dots, mut coverage) = match self.supersampling {
+            Some(supersampling) => {
+                downsample(&self.screen_buffer, &self.coverage_buffer, supersampling)
+            }
+            None => (self.screen_buffer.clone(), self.coverage_buffer.clone()),
+        };
+        if let Some(dithering) = self.dithering {
+            dither::dither(&mut dots, &mut coverage, dithering);
+        }
+        self.viewport.draw_chars(&dots, &coverage, self.clear_color);
     }
 
-    /// Clears the screen buffer.
+    /// Clears the screen buffer, the depth buffer, queued transparent faces and the viewport's
+    /// overlay text.
     pub fn clear(&mut self) {
-        let viewport_size = self.viewport.size();
+        self.viewport.clear_text();
+        self.transparent_faces.clear();
+        let buffer_size = self.buffer_size();
+        let clear_color = self.clear_color.unwrap_or(Color::BLACK);
         self.screen_buffer =
-            vec![vec![Color(0, 0, 0); viewport_size.1 as usize]; viewport_size.0 as usize];
+            vec![vec![clear_color; buffer_size.1 as usize]; buffer_size.0 as usize];
+        self.coverage_buffer = vec![vec![false; buffer_size.1 as usize]; buffer_size.0 as usize];
+        self.depth_buffer =
+            vec![vec![f32::INFINITY; buffer_size.1 as usize]; buffer_size.0 as usize];
+    }
+
+    /// Number of samples per dot along each axis.
+    fn sample_factor(&self) -> u16 {
+        self.supersampling
+            .map_or(1, |supersampling| supersampling.factor as u16)
+    }
+
+    /// Size of the screen buffer in samples.
+    fn buffer_size(&self) -> (u16, u16) {
+        let (width, height) = self.viewport.size();
+        let factor = self.sample_factor();
+        (width * factor, height * factor)
     }
 
     /// Draws a [`Model`] to the screen buffer. calling [`render`] afterwards will render the model
     /// to the screen.
     ///
+    /// Faces of models with a transparent [`Model::material`] are queued instead, and blended in
+    /// [`draw_transparent`] once everything opaque has been drawn.
+    ///
     /// [`render`]: #method.render
+    /// [`draw_transparent`]: #method.draw_transparent
     pub fn draw_object(&mut self, model: &Model) {
-        let model_view_matrix = model.model_matrix() * self.camera.view_matrix;
+        let model_view_matrix = self.camera.view_matrix * model.model_matrix();
         let mvp_matrix = self.camera.projection.as_matrix() * model_view_matrix.to_homogeneous();
 
-        let (width, height) = self.viewport.size();
+        let (width, height) = self.buffer_size();
 
+        // Direction the light travels in. Outward normals that point against it are lit
         let light = Vector3::new(0.0, 0.0, -1.0);
+        let cull_mode = model.cull_mode.unwrap_or(self.cull_mode);
+        let render_mode = model.render_mode.unwrap_or(self.render_mode);
+        let line_style = model.line_style.unwrap_or(self.line_style);
+        let material = model.material.unwrap_or_default();
+
+        if render_mode == RenderMode::PointCloud || model.index_buffer.is_empty() {
+            let point_style = model.point_style.unwrap_or(self.point_style);
+            self.draw_point_cloud(&model.vertex_buffer, mvp_matrix, point_style, material);
+            self.draw_model_gizmos(model);
+            return;
+        }
+
+        let (vertex_buffer, index_buffer) = self.level_of_detail(model, &model_view_matrix);
+        let mut faces: Vec<ProjectedFace> = Vec::with_capacity(index_buffer.len());
+        // Clip space corners of the faces that survive culling, for drawing edges and points.
+        // These are clipped before they are projected, as corners behind the camera would land
+        // anywhere on the screen
+        let mut outlines: Vec<[Vector4<f32>; 3]> = Vec::with_capacity(index_buffer.len());
+
+        for face in index_buffer {
+            let Some([v0, v1, v2]) = face.vertices(vertex_buffer) else {
+                continue;
+            };
 
-        for face in &model.index_buffer {
-            let mut v0 = model.vertex_at(face.indexes.0 - 1);
-            let mut v1 = model.vertex_at(face.indexes.1 - 1);
-            let mut v2 = model.vertex_at(face.indexes.2 - 1);
+            let mut normal = (v1.position - v0.position).cross(&(v2.position - v0.position));
 
-            let mut normal = (v2.position - v0.position).cross(&(v1.position - v0.position));
+            // Lit from the camera, so normals are rotated into view space
+            normal = model_view_matrix.rotation * normal.normalize();
+            let face_intensity = (-normal).dot(&light).clamp(0.0, 1.0);
+            let vertex_normals = (v0.normal, v1.normal, v2.normal);
+            let light_intensities = match (self.lighting, model.shading, vertex_normals) {
+                (false, _, _) => [1.0; 3],
+                (true, Shading::Smooth, (Some(n0), Some(n1), Some(n2))) => {
+                    [n0, n1, n2].map(|normal| {
+                        (model_view_matrix.rotation * -normal.normalize())
+                            .dot(&light)
+                            .clamp(0.0, 1.0)
+                    })
+                }
+                _ => [face_intensity; 3],
+            };
 
-            normal = model.rotation * normal.normalize();
-            let light_intensity = normal.dot(&light);
-            let intensity = (light_intensity * 255.0) as u8;
+            let vertex_colors = [v0.color, v1.color, v2.color].map(|c| c.unwrap_or(Color::WHITE));
+            let base_colors = match (face.color, model.shading) {
+                (Some(color), _) => [color; 3],
+                (None, Shading::Smooth) => vertex_colors,
+                (None, Shading::Flat) => {
+                    [Color::mean(vertex_colors).expect("Faces have three vertices"); 3]
+                }
+            };
+            let colors = [0, 1, 2].map(|i| base_colors[i] * material.color * light_intensities[i]);
+            let vertex_alphas = [v0.alpha, v1.alpha, v2.alpha].map(|a| a.unwrap_or(1.0));
+            let alphas = match (face.alpha, model.shading) {
+                (Some(alpha), _) => [alpha; 3],
+                (None, Shading::Smooth) => vertex_alphas,
+                (None, Shading::Flat) => [vertex_alphas.iter().sum::<f32>() / 3.0; 3],
+            }
+            .map(|alpha| alpha.clamp(0.0, 1.0) * material.alpha);
+
+            // Only the part in front of the camera is projected
+            let corners = [v0, v1, v2].map(|v| mvp_matrix * v.position.to_homogeneous());
+            let (polygon, len) =
+                clip_to_near_plane([0, 1, 2].map(|i| (corners[i], colors[i], alphas[i])));
+            if len < 3 {
+                continue;
+            }
+            let projected = polygon.map(|(corner, _, _)| {
+                let mut vertex = Vertex::new(Point3::from(corner.xyz() / corner.w));
+                vertex.map_to_screen(width as f32, height as f32);
+                vertex
+            });
+
+            let screen = |i: usize| projected[i].position.xy();
+            let area: f32 = (1..len - 1)
+                .map(|i| edge_function(&screen(0), &screen(i), &screen(i + 1)))
+                .sum();
+            let winding = Winding::from_area(area);
+            if cull_mode.culls(winding, self.front_face) {
+                continue;
+            }
+
+            outlines.push(corners);
+            for i in 1..len - 1 {
+                // The rasteriser expects counter-clockwise triangles
+                let order = match winding {
+                    Winding::CounterClockwise => [0, i, i + 1],
+                    Winding::Clockwise => [0, i + 1, i],
+                };
+                faces.push(ProjectedFace {
+                    vertices: order.map(|j| projected[j].clone()),
+                    colors: order.map(|j| polygon[j].1),
+                    alphas: order.map(|j| polygon[j].2),
+                });
+            }
+        }
 
-            v0.project(mvp_matrix, width as f32, height as f32);
-            v1.project(mvp_matrix, width as f32, height as f32);
-            v2.project(mvp_matrix, width as f32, height as f32);
+        match render_mode {
+            RenderMode::Solid | RenderMode::SolidWireframe => {
+                for face in &faces {
+                    let [v0, v1, v2] = &face.vertices;
+                    let opaque = face.alphas.iter().all(|&alpha| alpha >= 1.0);
+                    if opaque && !material.is_transparent() {
+                        Self::draw_triangle(self, v0, v1, v2, Some(face.colors), None);
+                        continue;
+                    }
+                    let depth = face
+                        .vertices
+                        .iter()
+                        .map(|v| self.camera.linearize_depth(v.position.z))
+                        .sum::<f32>()
+                        / 3.0;
+                    self.transparent_faces.push(TransparentFace {
+                        vertices: face.vertices.clone(),
+                        colors: face.colors,
+                        alphas: face.alphas,
+                        blend_mode: material.blend_mode,
+                        depth,
+                    });
+                }
+            }
+            RenderMode::HiddenLine => {
+                // Faces only occlude the edges drawn after them
+                for face in &faces {
+                    let [v0, v1, v2] = &face.vertices;
+                    Self::draw_triangle(self, v0, v1, v2, None, None);
+                }
+            }
+            RenderMode::Wireframe | RenderMode::Points | RenderMode::PointCloud => {}
+        }
 
-            if self.wireframe {
-                Self::draw_line(self, &v0, &v1);
-                Self::draw_line(self, &v1, &v2);
-                Self::draw_line(self, &v2, &v0);
-            } else if intensity > 0 {
-                Self::draw_triangle(self, &v0, &v1, &v2, Color(intensity, intensity, intensity));
+        match render_mode {
+            RenderMode::Wireframe => {
+                for &[p0, p1, p2] in &outlines {
+                    self.draw_clipped_edge(p0, p1, line_style, false);
+                    self.draw_clipped_edge(p1, p2, line_style, false);
+                    self.draw_clipped_edge(p2, p0, line_style, false);
+                }
+            }
+            RenderMode::SolidWireframe | RenderMode::HiddenLine => {
+                for &[p0, p1, p2] in &outlines {
+                    self.draw_clipped_edge(p0, p1, line_style, true);
+                    self.draw_clipped_edge(p1, p2, line_style, true);
+                    self.draw_clipped_edge(p2, p0, line_style, true);
+                }
             }
+            RenderMode::Points => {
+                for corner in outlines.iter().flatten() {
+                    let mut vertex = Vertex::new(Point3::from(corner.xyz() / corner.w));
+                    if corner.w <= 0.0 || !(-1.0..=1.0).contains(&vertex.position.z) {
+                        continue;
+                    }
+                    vertex.map_to_screen(width as f32, height as f32);
+                    let depth = self.camera.linearize_depth(vertex.position.z);
+                    Self::draw_dot(
+                        self,
+                        vertex.position.x as i32,
+                        vertex.position.y as i32,
+                        Some(depth),
+                        line_style,
+                    );
+                }
+            }
+            RenderMode::Solid | RenderMode::PointCloud => {}
         }
+        self.draw_model_gizmos(model);
     }
 
-    fn draw_triangle(&mut self, v0: &Vertex, v1: &Vertex, v2: &Vertex, color: Color) {
+    /// Draws every vertex as a square dot, depth tested against and written to the depth buffer.
+    /// Points of transparent materials or vertices are blended instead, without writing their
+    /// depth.
+    fn draw_point_cloud(
+        &mut self,
+        vertices: &[Vertex],
+        mvp_matrix: Matrix4<f32>,
+        style: PointStyle,
+        material: Material,
+    ) {
+        let (width, height) = self.buffer_size();
+        let background = self.clear_color.unwrap_or(Color::BLACK);
+
+        for vertex in vertices {
+            let mut point = Vertex::new(vertex.position);
+            point.project(mvp_matrix, width as f32, height as f32);
+            let position = point.position;
+            if !(-1.0..=1.0).contains(&position.z) {
+                continue;
+            }
+            let Some((columns, rows)) =
+                self.sample_square(position.x as i32, position.y as i32, style.size)
+            else {
+                continue;
+            };
+            let depth = self.camera.linearize_depth(position.z);
+
+            let mut color = vertex.color.unwrap_or(style.color) * material.color;
+            if let Some(fade) = style.fade {
+                let amount = fade.amount(depth);
+                if amount >= 1.0 {
+                    continue;
+                }
+                color = color.lerp(background, amount);
+            }
+
+            let alpha = vertex.alpha.unwrap_or(1.0).clamp(0.0, 1.0) * material.alpha;
+            let transparent = alpha < 1.0 || material.is_transparent();
+            for x in columns {
+                for y in rows.clone() {
+                    if transparent {
+                        let mode = material.blend_mode;
+                        Self::draw_pixel_blended(self, x, y, depth, color, alpha, mode);
+                    } else {
+                        Self::draw_pixel_depth(self, x, y, depth, Some(color));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Picks the least detailed version of the model that still has enough faces for its size on
+    /// the screen, estimated from its bounding sphere.
+    fn level_of_detail<'a>(
+        &self,
+        model: &'a Model,
+        model_view_matrix: &Isometry3<f32>,
+    ) -> (&'a [Vertex], &'a [Face]) {
+        let full = (&model.vertex_buffer[..], &model.index_buffer[..]);
+        if model.lods.is_empty() {
+            return full;
+        }
+        let Some(sphere) = model.bounding_sphere() else {
+            return full;
+        };
+
+        let distance = -model_view_matrix.transform_point(&sphere.center).z;
+        if distance <= self.camera.projection.znear() {
+            return full;
+        }
+        // Projected like the vertices, which are scaled by the full height of the buffer
+        let height = self.buffer_size().1 as f32;
+        let focal_length = self.camera.projection.as_matrix()[(1, 1)];
+        let radius = sphere.radius * focal_length / distance * height;
+        let wanted_faces = std::f32::consts::PI * radius * radius / LOD_SAMPLES_PER_FACE;
+
+        model
+            .lods
+            .iter()
+            .take_while(|lod| lod.index_buffer.len() as f32 >= wanted_faces)
+            .last()
+            .map_or(full, |lod| (&lod.vertex_buffer[..], &lod.index_buffer[..]))
+    }
+
+    /// Writes a text label at the terminal cell where a point in world space ends up on the
+    /// screen. The label starts at that cell and is drawn over everything else. Points behind the
+    /// camera or outside the viewport are not labelled.
+    pub fn draw_label(&mut self, position: Point3<f32>, text: &str, style: TextStyle) {
+        let mvp_matrix =
+            self.camera.projection.as_matrix() * self.camera.view_matrix.to_homogeneous();
+        let (width, height) = self.buffer_size();
+
+        let mut anchor = Vertex::new(position);
+        anchor.project(mvp_matrix, width as f32, height as f32);
+        let position = anchor.position;
+
+        if !(-1.0..=1.0).contains(&position.z)
+            || !(0.0..width as f32).contains(&position.x)
+            || !(0.0..height as f32).contains(&position.y)
+        {
+            return;
+        }
+        // Screen buffer rows go up, terminal rows go down
+        let factor = self.sample_factor();
+        let column = position.x as u16 / factor / 2;
+        let row = (height - 1 - position.y as u16) / factor / 4;
+        self.viewport.draw_text(column, row, text, style);
+    }
+
+    /// Draws the queued faces of transparent models, from the farthest to the nearest, and
+    /// empties the queue. They are hidden behind opaque geometry, but do not hide each other.
+    ///
+    /// [`render`] calls this, so it is only needed to draw something on top of transparent
+    /// models.
+    ///
+    /// [`render`]: #method.render
+    pub fn draw_transparent(&mut self) {
+        let mut faces = std::mem::take(&mut self.transparent_faces);
+        faces.sort_by(|a, b| b.depth.total_cmp(&a.depth));
+        for face in &faces {
+            let [v0, v1, v2] = &face.vertices;
+            let blend = Some((face.alphas, face.blend_mode));
+            Self::draw_triangle(self, v0, v1, v2, Some(face.colors), blend);
+        }
+    }
+
+    /// Rasterises a counter-clockwise triangle with depth testing, blending the colours of the
+    /// vertices across it. Passing `None` as the colours only writes to the depth buffer. With
+    /// `blend`, the opacity of each vertex and the blend mode, the triangle is blended onto the
+    /// screen buffer without writing its depth.
+    fn draw_triangle(
+        &mut self,
+        v0: &Vertex,
+        v1: &Vertex,
+        v2: &Vertex,
+        colors: Option<[Color; 3]>,
+        blend: Option<([f32; 3], BlendMode)>,
+    ) {
         let (bbmin, bbmax) = bounding_box(v0, v1, v2);
         let p0 = Point2::new(v0.position.x, v0.position.y);
         let p1 = Point2::new(v1.position.x, v1.position.y);
         let p2 = Point2::new(v2.position.x, v2.position.y);
 
-        for x in bbmin.0..bbmax.0 {
-            for y in bbmin.1..bbmax.1 {
+        let area = edge_function(&p0, &p1, &p2);
+        if area <= 0.0 {
+            return;
+        }
+
+        // Colours are interpolated in linear light, and in view space to keep them from warping
+        let flat = colors
+            .filter(|[c0, c1, c2]| c0 == c1 && c1 == c2)
+            .map(|[c0, _, _]| c0);
+        let linear_colors = colors.map(|colors| colors.map(Color::to_linear));
+        let inverse_depths = [v0, v1, v2].map(|v| 1.0 / self.camera.linearize_depth(v.position.z));
+
+        let (x_size, y_size) = self.buffer_size();
+        for x in bbmin.0.max(0)..bbmax.0.min(x_size as i16) {
+            for y in bbmin.1.max(0)..bbmax.1.min(y_size as i16) {
                 let screen_point = Point2::new(x as f32, y as f32);
 
-                let mut inside = true;
-                inside &= 0.0 <= edge_function(&p0, &p1, &screen_point);
-                inside &= 0.0 <= edge_function(&p1, &p2, &screen_point);
-                inside &= 0.0 <= edge_function(&p2, &p0, &screen_point);
+                let w0 = edge_function(&p1, &p2, &screen_point);
+                let w1 = edge_function(&p2, &p0, &screen_point);
+                let w2 = edge_function(&p0, &p1, &screen_point);
 
-                if inside {
-                    Self::draw_pixel(self, x, y, color);
+                if 0.0 <= w0 && 0.0 <= w1 && 0.0 <= w2 {
+                    // Depth in normalized device coordinates is linear in screen space
+                    let z = (w0 * v0.position.z + w1 * v1.position.z + w2 * v2.position.z) / area;
+                    let depth = self.camera.linearize_depth(z);
+                    let weights = [
+                        w0 * inverse_depths[0],
+                        w1 * inverse_depths[1],
+                        w2 * inverse_depths[2],
+                    ];
+                    let total = weights[0] + weights[1] + weights[2];
+                    let color = flat.or_else(|| {
+                        let [l0, l1, l2] = linear_colors?;
+                        let linear = (l0 * weights[0] + l1 * weights[1] + l2 * weights[2]) / total;
+                        Some(Color::from_linear(linear))
+                    });
+                    match (blend, color) {
+                        (Some(([a0, a1, a2], mode)), Some(color)) => {
+                            let alpha =
+                                (a0 * weights[0] + a1 * weights[1] + a2 * weights[2]) / total;
+                            Self::draw_pixel_blended(self, x, y, depth, color, alpha, mode)
+                        }
+                        _ => Self::draw_pixel_depth(self, x, y, depth, color),
+                    }
                 }
             }
         }
     }
 
-    fn draw_pixel(&mut self, x: i16, y: i16, color: Color) {
-        let x_size = self.viewport.size().0 as i16;
-        let y_size = self.viewport.size().1 as i16;
+    /// Draws a single dot to the screen buffer. Dots outside the viewport are ignored.
+    ///
+    /// Coordinates are in dots, with the origin in the lower left corner of the viewport and the
+    /// y-axis pointing up. This is the same space 3D-objects are projected into, so 2D and 3D
+    /// draw calls can be mixed freely; later draws end up on top.
+    pub fn draw_pixel(&mut self, x: i16, y: i16, color: Color) {
+        let Some((xs, ys)) = self.dot_samples(x, y) else {
+            return;
+        };
+        for sx in xs {
+            for sy in ys.clone() {
+                Self::draw_sample(self, sx, sy, color);
+            }
+        }
+    }
+
+    /// Blends a single dot onto the screen buffer, with `alpha` as the opacity of the colour
+    /// between 0 and 1. Otherwise the same as [`draw_pixel`].
+    ///
+    /// [`draw_pixel`]: #method.draw_pixel
+    pub fn blend_pixel(&mut self, x: i16, y: i16, color: Color, alpha: f32, mode: BlendMode) {
+        let Some((xs, ys)) = self.dot_samples(x, y) else {
+            return;
+        };
+        for sx in xs {
+            for sy in ys.clone() {
+                Self::draw_pixel_blended(self, sx, sy, f32::NEG_INFINITY, color, alpha, mode);
+            }
+        }
+    }
+
+    /// Columns and rows of the samples that make up a dot, or `None` for dots outside the
+    /// viewport.
+    fn dot_samples(&self, x: i16, y: i16) -> Option<(Range<i16>, Range<i16>)> {
+        let (width, height) = self.viewport.size();
+        if x < 0 || y < 0 || x as i32 >= width as i32 || y as i32 >= height as i32 {
+            return None;
+        }
+        // Inside the viewport, so the samples are inside the screen buffer
+        let factor = self.sample_factor() as i32;
+        let samples = |dot: i16| (dot as i32 * factor) as i16..((dot as i32 + 1) * factor) as i16;
+        Some((samples(x), samples(y)))
+    }
+
+    /// Columns and rows of the samples in a square `size` dots wide around a sample, cut to the
+    /// screen buffer, or `None` if none of them are on it.
+    fn sample_square(&self, x: i32, y: i32, size: u16) -> Option<(Range<i16>, Range<i16>)> {
+        let size = size.max(1) as i32 * self.sample_factor() as i32;
+        let offset = (size - 1) / 2;
+        let (width, height) = self.buffer_size();
+        let cut = |center: i32, length: u16| {
+            let start = center.saturating_sub(offset);
+            let end = start.saturating_add(size).min(length as i32);
+            let start = start.max(0);
+            (start < end).then_some(start as i16..end as i16)
+        };
+        Some((cut(x, width)?, cut(y, height)?))
+    }
+
+    /// Writes a single sample of the screen buffer. Same as [`draw_pixel`] when supersampling is
+    /// disabled.
+    ///
+    /// [`draw_pixel`]: #method.draw_pixel
+    fn draw_sample(&mut self, x: i16, y: i16, color: Color) {
+        let x_size = self.buffer_size().0 as i16;
+        let y_size = self.buffer_size().1 as i16;
         if x >= 0 && x < x_size && y >= 0 && y < y_size {
             self.screen_buffer[x as usize][y as usize] = color;
+            self.coverage_buffer[x as usize][y as usize] = true;
+        }
+    }
+
+    /// Draws a pixel if it is not behind what has already been drawn, and stores its depth.
+    /// Passing `None` as the colour only writes to the depth buffer.
+    fn draw_pixel_depth(&mut self, x: i16, y: i16, depth: f32, color: Option<Color>) {
+        let x_size = self.buffer_size().0 as i16;
+        let y_size = self.buffer_size().1 as i16;
+        if x >= 0 && x < x_size && y >= 0 && y < y_size {
+            let stored = &mut self.depth_buffer[x as usize][y as usize];
+            if depth <= *stored {
+                *stored = depth;
+                if let Some(color) = color {
+                    self.screen_buffer[x as usize][y as usize] = color;
+                    self.coverage_buffer[x as usize][y as usize] = true;
+                }
+            }
         }
     }
 
-    fn draw_line(&mut self, v1: &Vertex, v2: &Vertex) {
-        let mut x1 = v1.position.x as i16;
-        let mut y1 = v1.position.y as i16;
-        let x2 = v2.position.x as i16;
-        let y2 = v2.position.y as i16;
-        let dx = (x2 - x1).abs();
-        let dy = -(y2 - y1).abs();
-        let sx = if x1 < x2 { 1 } else { -1 };
-        let sy = if y1 < y2 { 1 } else { -1 };
-        let mut err = dy + dx;
-        let mut err2;
-
-        loop {
-            Self::draw_pixel(self, x1, y1, Color(255, 255, 255));
-            if x1 == x2 && y1 == y2 {
-                break;
-            }
-            err2 = 2 * err;
-            if err2 >= dy {
-                if x1 == x2 {
-                    break;
+    /// Blends a pixel onto the screen buffer if it is not behind what has already been drawn,
+    /// leaving the depth buffer untouched. Pixels that have not been drawn to are blended with
+    /// the clear colour, or black without one.
+    fn draw_pixel_blended(
+        &mut self,
+        x: i16,
+        y: i16,
+        depth: f32,
+        color: Color,
+        alpha: f32,
+        mode: BlendMode,
+    ) {
+        let x_size = self.buffer_size().0 as i16;
+        let y_size = self.buffer_size().1 as i16;
+        if x >= 0
+            && x < x_size
+            && y >= 0
+            && y < y_size
+            && depth <= self.depth_buffer[x as usize][y as usize]
+        {
+            let (x, y) = (x as usize, y as usize);
+            let destination = if self.coverage_buffer[x][y] {
+                self.screen_buffer[x][y]
+            } else {
+                self.clear_color.unwrap_or(Color::BLACK)
+            };
+            self.screen_buffer[x][y] = mode.blend(color, destination, alpha);
+            self.coverage_buffer[x][y] = true;
+        }
+    }
+
+    /// Draws a square dot, as wide as the line thickness, centered on the pixel. Dots with a
+    /// depth are depth tested with a bias, but do not write to the depth buffer.
+    fn draw_dot(&mut self, x: i32, y: i32, depth: Option<f32>, style: LineStyle) {
+        let Some((columns, rows)) = self.sample_square(x, y, style.thickness) else {
+            return;
+        };
+        for x in columns {
+            for y in rows.clone() {
+                match depth {
+                    Some(depth) => Self::draw_line_pixel(self, x, y, depth, style.color),
+                    None => Self::draw_sample(self, x, y, style.color),
                 }
-                err += dy;
-                x1 += sx;
             }
-            if err2 <= dx {
-                if y1 == y2 {
-                    break;
+        }
+    }
+
+    /// Draws a pixel of a line or point if it is not clearly behind what has already been drawn.
+    fn draw_line_pixel(&mut self, x: i16, y: i16, depth: f32, color: Color) {
+        let x_size = self.buffer_size().0 as i16;
+        let y_size = self.buffer_size().1 as i16;
+        if x >= 0
+            && x < x_size
+            && y >= 0
+            && y < y_size
+            && depth * (1.0 - LINE_DEPTH_BIAS) <= self.depth_buffer[x as usize][y as usize]
+        {
+            self.screen_buffer[x as usize][y as usize] = color;
+            self.coverage_buffer[x as usize][y as usize] = true;
+        }
+    }
+
+    /// Draws a line between two projected vertices. With `depth_test`, parts of the line behind
+    /// already drawn faces are skipped.
+    fn draw_edge(&mut self, v1: &Vertex, v2: &Vertex, style: LineStyle, depth_test: bool) {
+        let from = (v1.position.x as i16, v1.position.y as i16);
+        let to = (v2.position.x as i16, v2.position.y as i16);
+        let line = Line::new(from, to);
+        let steps = line.len().saturating_sub(1).max(1) as f32;
+
+        for (step, (x, y)) in line.enumerate() {
+            let depth = depth_test.then(|| {
+                let t = step as f32 / steps;
+                let z = v1.position.z + (v2.position.z - v1.position.z) * t;
+                self.camera.linearize_depth(z)
+            });
+            Self::draw_dot(self, x.into(), y.into(), depth, style);
+        }
+    }
+}
+
+/// Reduces a supersampled buffer and its coverage to one colour per dot.
+fn downsample(
+    samples: &[Vec<Color>],
+    coverage: &[Vec<bool>],
+    supersampling: Supersampling,
+) -> (Vec<Vec<Color>>, Vec<Vec<bool>>) {
+    let factor = supersampling.factor as usize;
+    let (width, height) = (samples.len() / factor, samples[0].len() / factor);
+    let needed = supersampling.coverage_threshold * (factor * factor) as f32;
+
+    let mut dots = vec![vec![Color::BLACK; height]; width];
+    let mut dot_coverage = vec![vec![false; height]; width];
+    let mut lit: Vec<Color> = Vec::with_capacity(factor * factor);
+    for x in 0..width {
+        for y in 0..height {
+            lit.clear();
+            for sx in x * factor..(x + 1) * factor {
+                for sy in y * factor..(y + 1) * factor {
+                    if coverage[sx][sy] {
+                        lit.push(samples[sx][sy]);
+                    }
                 }
-                err += dx;
-                y1 += sy;
             }
+            if !lit.is_empty() && lit.len() as f32 >= needed {
+                dots[x][y] = Color::mean(lit.iter().copied()).expect("Samples are lit");
+                dot_coverage[x][y] = true;
+            }
+        }
+    }
+    (dots, dot_coverage)
+}
+
+/// Iterator over the pixels of a line between two screen points, using Bresenham's algorithm.
+/// Both end points are included.
+#[derive(Clone, Debug)]
+struct Line {
+    // Wider than the end points, so that the steps do not overflow
+    x: i32,
+    y: i32,
+    x2: i32,
+    y2: i32,
+    dx: i32,
+    dy: i32,
+    sx: i32,
+    sy: i32,
+    err: i32,
+    done: bool,
+}
+
+impl Line {
+    fn new(from: (i16, i16), to: (i16, i16)) -> Line {
+        let (from, to) = ((from.0 as i32, from.1 as i32), (to.0 as i32, to.1 as i32));
+        let dx = (to.0 - from.0).abs();
+        let dy = -(to.1 - from.1).abs();
+        Line {
+            x: from.0,
+            y: from.1,
+            x2: to.0,
+            y2: to.1,
+            dx,
+            dy,
+            sx: if from.0 < to.0 { 1 } else { -1 },
+            sy: if from.1 < to.1 { 1 } else { -1 },
+            err: dx + dy,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for Line {
+    type Item = (i16, i16);
+
+    fn next(&mut self) -> Option<(i16, i16)> {
+        if self.done {
+            return None;
+        }
+        // Every point lies between the end points, so it fits
+        let point = (self.x as i16, self.y as i16);
+        if self.x == self.x2 && self.y == self.y2 {
+            self.done = true;
+            return Some(point);
+        }
+        let err2 = 2 * self.err;
+        if err2 >= self.dy {
+            self.err += self.dy;
+            self.x += self.sx;
         }
+        if err2 <= self.dx {
+            self.err += self.dx;
+            self.y += self.sy;
+        }
+        Some(point)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = if self.done {
+            0
+        } else {
+            (self.x2 - self.x).abs().max((self.y2 - self.y).abs()) as usize + 1
+        };
+        (remaining, Some(remaining))
     }
 }
 
+impl ExactSizeIterator for Line {}
+
+/// Cuts off the part of a triangle in clip space that is between the camera and its near plane,
+/// interpolating the colours and opacities of its corners. Leaves a polygon of up to four
+/// corners, returned with their number.
+fn clip_to_near_plane(
+    corners: [(Vector4<f32>, Color, f32); 3],
+) -> ([(Vector4<f32>, Color, f32); 4], usize) {
+    // Positive on the side of the near plane the camera can see
+    let distance = |corner: &Vector4<f32>| corner.w + corner.z;
+    let mut polygon = [corners[0]; 4];
+    let mut len = 0;
+    for i in 0..3 {
+        let ((p0, c0, a0), (p1, c1, a1)) = (corners[i], corners[(i + 1) % 3]);
+        let (d0, d1) = (distance(&p0), distance(&p1));
+        if d0 >= 0.0 {
+            polygon[len] = (p0, c0, a0);
+            len += 1;
+        }
+        if (d0 >= 0.0) != (d1 >= 0.0) {
+            let t = d0 / (d0 - d1);
+            polygon[len] = (p0.lerp(&p1, t), c0.lerp(c1, t), a0 + (a1 - a0) * t);
+            len += 1;
+        }
+    }
+    (polygon, len)
+}
+
 // TODO: document, test, move to utils module?
 fn edge_function(v0: &Point2<f32>, v1: &Point2<f32>, v2: &Point2<f32>) -> f32 {
     -((v2.x - v0.x) * (v1.y - v0.y) - (v2.y - v0.y) * (v1.x - v0.x))
@@ -241,3 +1201,466 @@ fn bounding_box(v0: &Vertex, v1: &Vertex, v2: &Vertex) -> ((i16, i16), (i16, i16
 
     (min, max)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        camera::Camera,
+        downsample, edge_function,
+        material::BlendMode,
+        model::{Model, NoResolver},
+        viewport::Viewport,
+        Color, CullMode, DepthFade, Face, LineStyle, Material, PointStyle, RenderMode, Renderer,
+        Supersampling, TransparentFace, Vertex, Winding,
+    };
+    use nalgebra::{Isometry3, Point2, Point3, Vector3};
+    use std::ops::Range;
+
+    #[test]
+    fn test_winding_from_area() {
+        let p0 = Point2::new(0.0, 0.0);
+        let p1 = Point2::new(1.0, 0.0);
+        let p2 = Point2::new(0.0, 1.0);
+        assert_eq!(
+            Winding::CounterClockwise,
+            Winding::from_area(edge_function(&p0, &p1, &p2))
+        );
+        assert_eq!(
+            Winding::Clockwise,
+            Winding::from_area(edge_function(&p0, &p2, &p1))
+        );
+    }
+
+    #[test]
+    fn test_cull_modes() {
+        let ccw = Winding::CounterClockwise;
+        let cw = Winding::Clockwise;
+        assert!(!CullMode::None.culls(cw, ccw));
+        assert!(CullMode::Back.culls(cw, ccw));
+        assert!(!CullMode::Back.culls(ccw, ccw));
+        assert!(CullMode::Front.culls(ccw, ccw));
+        assert!(!CullMode::Back.culls(cw, cw));
+    }
+
+    #[test]
+    fn test_depth_buffer_keeps_nearest() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(4, 2, 0, 0));
+        let triangle = |z| {
+            [
+                Vertex::new(Point3::new(0.0, 0.0, z)),
+                Vertex::new(Point3::new(8.0, 0.0, z)),
+                Vertex::new(Point3::new(0.0, 8.0, z)),
+            ]
+        };
+
+        let [v0, v1, v2] = triangle(0.5);
+        renderer.draw_triangle(&v0, &v1, &v2, Some([Color(255, 0, 0); 3]), None);
+        let [v0, v1, v2] = triangle(0.9);
+        renderer.draw_triangle(&v0, &v1, &v2, Some([Color(0, 255, 0); 3]), None);
+        assert_eq!(255, renderer.screen_buffer[1][1].0);
+
+        let [v0, v1, v2] = triangle(0.1);
+        renderer.draw_triangle(&v0, &v1, &v2, Some([Color(0, 0, 255); 3]), None);
+        assert_eq!(255, renderer.screen_buffer[1][1].2);
+    }
+
+    #[test]
+    fn test_vertex_colors_are_interpolated() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+        let vertex = |x, y| Vertex::new(Point3::new(x, y, 0.5));
+        let colors = [Color::RED, Color::GREEN, Color::BLUE];
+        renderer.draw_triangle(
+            &vertex(0.0, 0.0),
+            &vertex(15.0, 0.0),
+            &vertex(0.0, 15.0),
+            Some(colors),
+            None,
+        );
+        assert_eq!(Color(255, 0, 0), renderer.screen_buffer[0][0]);
+        let near_green = renderer.screen_buffer[14][0];
+        assert!(near_green.1 > 240 && near_green.0 < 100 && near_green.2 == 0);
+        let middle = renderer.screen_buffer[5][5];
+        assert!(middle.0 > 100 && middle.1 > 100 && middle.2 > 100);
+    }
+
+    #[test]
+    fn test_transparent_faces_blend_back_to_front() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(4, 2, 0, 0));
+        let vertex = |x, y, z| Vertex::new(Point3::new(x, y, z));
+        let mut queue = |z: f32, color: Color, alpha: f32| {
+            let depth = renderer.camera.linearize_depth(z);
+            renderer.transparent_faces.push(TransparentFace {
+                vertices: [
+                    vertex(0.0, 0.0, z),
+                    vertex(8.0, 0.0, z),
+                    vertex(0.0, 8.0, z),
+                ],
+                colors: [color; 3],
+                alphas: [alpha; 3],
+                blend_mode: BlendMode::Alpha,
+                depth,
+            });
+        };
+        // Queued nearest first, an opaque red face hides the farthest one
+        queue(0.5, Color::BLUE, 0.5);
+        queue(0.95, Color::GREEN, 1.0);
+        renderer.draw_triangle(
+            &vertex(0.0, 0.0, 0.9),
+            &vertex(8.0, 0.0, 0.9),
+            &vertex(0.0, 8.0, 0.9),
+            Some([Color::RED; 3]),
+            None,
+        );
+
+        renderer.draw_transparent();
+        assert!(renderer.transparent_faces.is_empty());
+        assert_eq!(
+            Color::BLUE.over(Color::RED, 0.5),
+            renderer.screen_buffer[1][1]
+        );
+    }
+
+    #[test]
+    fn test_translucent_faces_and_points_are_blended() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+        renderer.set_cull_mode(CullMode::None);
+        let vertex = |x, y| Vertex::new(Point3::new(x, y, -5.0));
+        let mut triangle = Model::from_buffers(
+            vec![vertex(-1.0, -1.0), vertex(1.0, -1.0), vertex(0.0, 1.0)],
+            vec![Face::new(0, 1, 2)],
+        )
+        .unwrap();
+        renderer.draw_object(&triangle);
+        assert!(renderer.transparent_faces.is_empty());
+
+        triangle.index_buffer[0].alpha = Some(0.25);
+        renderer.draw_object(&triangle);
+        assert_eq!(1, renderer.transparent_faces.len());
+        assert_eq!([0.25; 3], renderer.transparent_faces[0].alphas);
+        renderer.draw_transparent();
+
+        let point = Vertex {
+            color: Some(Color::RED),
+            alpha: Some(0.5),
+            ..vertex(0.0, 0.0)
+        };
+        let cloud = Model::from_buffers(vec![point], vec![]).unwrap();
+        let background = renderer.screen_buffer[8][8];
+        renderer.draw_object(&cloud);
+        assert_eq!(
+            Color::RED.over(background, 0.5),
+            renderer.screen_buffer[8][8]
+        );
+    }
+
+    #[test]
+    fn test_level_of_detail_follows_screen_size() {
+        let renderer = Renderer::new(Viewport::with_size_and_pos(80, 48, 0, 0));
+        let mut plane = Model::new_plane(16, 2.0);
+        plane.generate_lods(4);
+
+        let faces_at = |plane: &Model| {
+            let model_view_matrix = renderer.camera.view_matrix * plane.model_matrix();
+            renderer.level_of_detail(plane, &model_view_matrix).1.len()
+        };
+        plane.translate(0.0, 0.0, -2.0);
+        assert_eq!(plane.index_buffer.len(), faces_at(&plane));
+        plane.translate(0.0, 0.0, -500.0);
+        assert_eq!(plane.lods[3].index_buffer.len(), faces_at(&plane));
+    }
+
+    #[test]
+    fn test_mesh_changes_are_drawn_at_a_distance() {
+        let renderer = Renderer::new(Viewport::with_size_and_pos(80, 48, 0, 0));
+        let mut plane = Model::new_plane(16, 2.0);
+        plane.generate_lods(4);
+        plane.normalize();
+        plane.translate(0.0, 0.0, -500.0);
+
+        let model_view_matrix = renderer.camera.view_matrix * plane.model_matrix();
+        let (vertices, faces) = renderer.level_of_detail(&plane, &model_view_matrix);
+        assert_eq!(plane.index_buffer.len(), faces.len());
+        assert!(vertices
+            .iter()
+            .zip(&plane.vertex_buffer)
+            .all(|(drawn, vertex)| drawn.position == vertex.position));
+    }
+
+    #[test]
+    fn test_point_cloud_far_off_screen() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+        renderer.set_point_style(PointStyle {
+            size: u16::MAX,
+            ..PointStyle::default()
+        });
+        renderer.set_supersampling(Some(Supersampling::default()));
+        let cloud = Model::from_buffers(
+            vec![
+                Vertex::new(Point3::new(1e9, 0.0, -5.0)),
+                Vertex::new(Point3::new(0.0, -1e9, -5.0)),
+            ],
+            vec![],
+        )
+        .unwrap();
+        renderer.draw_object(&cloud);
+        assert!(renderer.coverage_buffer.iter().flatten().all(|&c| !c));
+    }
+
+    #[test]
+    fn test_point_cloud_keeps_nearest_points() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+        let point = |z, color| Vertex {
+            color: Some(color),
+            ..Vertex::new(Point3::new(0.0, 0.0, z))
+        };
+        let cloud = Model::from_buffers(
+            vec![point(-2.0, Color::GREEN), point(-5.0, Color::RED)],
+            vec![],
+        )
+        .unwrap();
+        renderer.set_point_style(PointStyle {
+            size: 2,
+            ..PointStyle::default()
+        });
+        renderer.draw_object(&cloud);
+        assert_eq!(Color::GREEN, renderer.screen_buffer[8][8]);
+        assert_eq!(Color::GREEN, renderer.screen_buffer[9][9]);
+        assert_eq!(
+            4,
+            renderer
+                .coverage_buffer
+                .iter()
+                .flatten()
+                .filter(|&&c| c)
+                .count()
+        );
+
+        // The nearer point is part way through the fade
+        renderer.clear();
+        renderer.set_point_style(PointStyle {
+            fade: Some(DepthFade {
+                near: 1.0,
+                far: 4.0,
+            }),
+            ..PointStyle::default()
+        });
+        renderer.draw_object(&cloud);
+        let faded = renderer.screen_buffer[8][8];
+        assert!(faded.1 > 0 && faded.1 < 255 && faded.0 == 0);
+    }
+
+    #[test]
+    fn test_black_is_drawn() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(4, 2, 0, 0));
+        renderer.set_clear_color(Some(Color(0, 0, 255)));
+        assert!(!renderer.coverage_buffer[1][1]);
+        assert_eq!(255, renderer.screen_buffer[1][1].2);
+
+        renderer.draw_pixel(1, 1, Color(0, 0, 0));
+        assert!(renderer.coverage_buffer[1][1]);
+        assert_eq!(0, renderer.screen_buffer[1][1].2);
+    }
+
+    #[test]
+    fn test_downsample_coverage() {
+        let white = Color(255, 255, 255);
+        let black = Color(0, 0, 0);
+        // One dot with all four samples lit, one with a single sample lit
+        let samples = vec![
+            vec![white, white],
+            vec![white, white],
+            vec![black, black],
+            vec![black, Color(100, 0, 0)],
+        ];
+        let coverage = vec![
+            vec![true, true],
+            vec![true, true],
+            vec![false, false],
+            vec![false, true],
+        ];
+        let supersampling = Supersampling {
+            factor: 2,
+            coverage_threshold: 0.25,
+        };
+        let (dots, dot_coverage) = downsample(&samples, &coverage, supersampling);
+        assert_eq!(255, dots[0][0].0);
+        assert_eq!(100, dots[1][0].0);
+        assert_eq!(vec![vec![true], vec![true]], dot_coverage);
+
+        let supersampling = Supersampling {
+            factor: 2,
+            coverage_threshold: 0.5,
+        };
+        let (_, dot_coverage) = downsample(&samples, &coverage, supersampling);
+        assert_eq!(vec![vec![true], vec![false]], dot_coverage);
+    }
+
+    #[test]
+    fn test_pixels_outside_the_viewport_are_ignored() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+        renderer.set_supersampling(Some(Supersampling {
+            factor: 4,
+            coverage_threshold: 0.5,
+        }));
+        for (x, y) in [
+            (i16::MAX, 0),
+            (0, i16::MAX),
+            (10000, 5),
+            (i16::MIN, -1),
+            (16, 0),
+        ] {
+            renderer.draw_pixel(x, y, Color::RED);
+            renderer.blend_pixel(x, y, Color::RED, 0.5, BlendMode::Alpha);
+        }
+        assert!(!renderer.coverage_buffer.iter().flatten().any(|&c| c));
+
+        renderer.draw_pixel(15, 15, Color::RED);
+        assert_eq!(Color::RED, renderer.screen_buffer[63][63]);
+        assert_eq!(Color::RED, renderer.screen_buffer[60][60]);
+    }
+
+    #[test]
+    fn test_camera_can_turn_around() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+        renderer.set_cull_mode(CullMode::None);
+        renderer.set_lighting(false);
+        let vertex = |x, y| Vertex::new(Point3::new(x, y, 5.0));
+        let mut triangle = Model::from_buffers(
+            vec![vertex(-2.0, -2.0), vertex(2.0, -2.0), vertex(0.0, 2.0)],
+            vec![Face::new(0, 1, 2)],
+        )
+        .unwrap();
+        triangle.material = Some(Material {
+            color: Color::RED,
+            ..Material::default()
+        });
+
+        // Behind the default camera
+        let mut camera = Camera::new(1.0, std::f32::consts::FRAC_PI_2, 1.0, 100.0);
+        camera.view_matrix = Isometry3::look_at_rh(
+            &Point3::origin(),
+            &Point3::new(0.0, 0.0, 1.0),
+            &Vector3::y(),
+        );
+        renderer.set_camera(camera);
+        renderer.draw_object(&triangle);
+        // Unlit, so drawn in its full colour
+        assert_eq!(Color::RED, renderer.screen_buffer[8][8]);
+    }
+
+    #[test]
+    fn test_faces_are_clipped_at_the_camera() {
+        // The last vertex is behind the camera, where it would be projected above the others
+        let triangle = Model::from_buffers(
+            vec![
+                Vertex::new(Point3::new(-1.0, -1.0, -5.0)),
+                Vertex::new(Point3::new(1.0, -1.0, -5.0)),
+                Vertex::new(Point3::new(0.0, -1.0, 5.0)),
+            ],
+            vec![Face::new(0, 1, 2)],
+        )
+        .unwrap();
+        for render_mode in [
+            RenderMode::Solid,
+            RenderMode::Wireframe,
+            RenderMode::SolidWireframe,
+            RenderMode::HiddenLine,
+            RenderMode::Points,
+        ] {
+            let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+            renderer.set_camera(Camera::new(1.0, std::f32::consts::FRAC_PI_2, 1.0, 100.0));
+            renderer.set_render_mode(render_mode);
+            renderer.set_cull_mode(CullMode::None);
+            renderer.draw_object(&triangle);
+
+            let drawn = |rows: Range<usize>| {
+                renderer
+                    .coverage_buffer
+                    .iter()
+                    .any(|column| column[rows.clone()].iter().any(|&c| c))
+            };
+            assert!(drawn(0..8), "{render_mode:?}");
+            assert!(!drawn(8..16), "{render_mode:?}");
+        }
+    }
+
+    #[test]
+    fn test_faces_out_of_range_are_skipped() {
+        let vertex = |x, y| Vertex::new(Point3::new(x, y, -5.0));
+        let mut triangle = Model::from_buffers(
+            vec![vertex(-1.0, -1.0), vertex(1.0, -1.0), vertex(0.0, 1.0)],
+            vec![Face::new(0, 1, 2)],
+        )
+        .unwrap();
+        triangle.index_buffer.push(Face::new(0, 1, 3));
+        for render_mode in [
+            RenderMode::Solid,
+            RenderMode::Wireframe,
+            RenderMode::HiddenLine,
+            RenderMode::Points,
+        ] {
+            let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+            renderer.set_render_mode(render_mode);
+            renderer.draw_object(&triangle);
+            renderer.draw_normals(&triangle, LineStyle::default());
+            assert!(renderer.coverage_buffer.iter().flatten().any(|&c| c));
+        }
+    }
+
+    #[test]
+    fn test_thick_points_far_off_screen() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+        renderer.set_render_mode(RenderMode::Points);
+        renderer.set_line_style(LineStyle {
+            color: Color::WHITE,
+            thickness: u16::MAX,
+        });
+        renderer.set_supersampling(Some(Supersampling::default()));
+        let vertex = |x| Vertex::new(Point3::new(x, 0.0, -5.0));
+        let triangle = Model::from_buffers(
+            vec![vertex(1e9), vertex(2e9), vertex(3e9)],
+            vec![Face::new(0, 1, 2)],
+        )
+        .unwrap();
+        renderer.draw_object(&triangle);
+        assert!(renderer.coverage_buffer.iter().flatten().all(|&c| !c));
+    }
+
+    #[test]
+    fn test_model_is_placed_before_the_camera_moves() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+        let mut camera = Camera::new(1.0, std::f32::consts::FRAC_PI_2, 1.0, 100.0);
+        camera.view_matrix = Isometry3::translation(0.0, 0.0, -5.0);
+        renderer.set_camera(camera);
+        renderer.set_cull_mode(CullMode::None);
+        renderer.set_lighting(false);
+        // Faces sideways until it is turned towards the camera
+        let vertex = |y, z| Vertex::new(Point3::new(0.0, y, z));
+        let mut triangle = Model::from_buffers(
+            vec![vertex(-0.5, -0.5), vertex(-0.5, 0.5), vertex(0.5, 0.0)],
+            vec![Face::new(0, 1, 2)],
+        )
+        .unwrap();
+        triangle.material = Some(Material {
+            color: Color::RED,
+            ..Material::default()
+        });
+        triangle.rotate(0.0, 90.0, 0.0);
+        triangle.translate(1.0, 0.0, 0.0);
+
+        renderer.draw_object(&triangle);
+        assert_eq!(Color::RED, renderer.screen_buffer[11][8]);
+        assert_ne!(Color::RED, renderer.screen_buffer[5][8]);
+    }
+
+    #[test]
+    fn test_file_normals_light_faces_towards_the_camera() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+        let obj = "v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvn 0 0 1\nf 1//1 2//1 3//1 4//1\n";
+        let mut quad = Model::from_obj(obj, &NoResolver).unwrap();
+        quad.translate(0.0, 0.0, -5.0);
+        renderer.draw_object(&quad);
+        assert!(renderer.coverage_buffer[8][8]);
+        assert_eq!(Color::WHITE, renderer.screen_buffer[8][8]);
+    }
+}
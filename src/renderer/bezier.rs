@@ -0,0 +1,18 @@
+use nalgebra::Point;
+
+/// Evaluates a Bézier curve at `t` by repeated linear interpolation of its control points, in
+/// any number of dimensions. The scratch buffer is reused between calls to avoid allocating.
+pub(crate) fn de_casteljau<const D: usize>(
+    control_points: &[Point<f32, D>],
+    t: f32,
+    scratch: &mut Vec<Point<f32, D>>,
+) -> Point<f32, D> {
+    scratch.clear();
+    scratch.extend_from_slice(control_points);
+    for level in (1..scratch.len()).rev() {
+        for i in 0..level {
+            scratch[i] = scratch[i] + (scratch[i + 1] - scratch[i]) * t;
+        }
+    }
+    scratch[0]
+}
@@ -1,7 +1,7 @@
 use nalgebra::{Isometry3, Perspective3, Point3, Vector3};
 
 /// Virtual camera, through which the scene is rendered.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct Camera {
     /// Used to move object into camera space.
     pub view_matrix: Isometry3<f32>,
@@ -26,4 +26,11 @@ impl Camera {
             projection,
         }
     }
+
+    /// Converts a depth in normalized device coordinates, as produced by [`Camera::projection`],
+    /// back to the distance from the camera along the view direction.
+    pub fn linearize_depth(&self, z: f32) -> f32 {
+        let (znear, zfar) = (self.projection.znear(), self.projection.zfar());
+        2.0 * znear * zfar / (zfar + znear - z * (zfar - znear))
+    }
 }
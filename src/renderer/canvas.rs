@@ -0,0 +1,254 @@
+use nalgebra::Point2;
+use std::ops::Range;
+
+use super::{bezier::de_casteljau, Color, Line, Renderer};
+
+impl Renderer {
+    /// Draws a straight line between two points, including both end points.
+    ///
+    /// Like with [`draw_pixel`], coordinates are in dots with the origin in the lower left corner.
+    ///
+    /// [`draw_pixel`]: #method.draw_pixel
+    pub fn draw_line(&mut self, from: (i16, i16), to: (i16, i16), color: Color) {
+        for (x, y) in Line::new(from, to) {
+            self.draw_pixel(x, y, color);
+        }
+    }
+
+    /// Draws lines connecting each point to the next one.
+    pub fn draw_polyline(&mut self, points: &[(i16, i16)], color: Color) {
+        for segment in points.windows(2) {
+            self.draw_line(segment[0], segment[1], color);
+        }
+        if let [point] = points {
+            self.draw_pixel(point.0, point.1, color);
+        }
+    }
+
+    /// Draws the outline of a rectangle. `corner` is the lower left corner, and the rectangle
+    /// covers `width` by `height` dots.
+    pub fn draw_rectangle(&mut self, corner: (i16, i16), width: u16, height: u16, color: Color) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let (x0, y0) = corner;
+        let (x1, y1) = (
+            saturate(x0 as i32 + width as i32 - 1),
+            saturate(y0 as i32 + height as i32 - 1),
+        );
+        self.draw_polyline(&[(x0, y0), (x1, y0), (x1, y1), (x0, y1), (x0, y0)], color);
+    }
+
+    /// Fills a rectangle. `corner` is the lower left corner, and the rectangle covers `width` by
+    /// `height` dots.
+    pub fn fill_rectangle(&mut self, corner: (i16, i16), width: u16, height: u16, color: Color) {
+        let (columns, rows) = self.dot_ranges();
+        let xs = corner.0 as i32..corner.0 as i32 + width as i32;
+        let ys = corner.1 as i32..corner.1 as i32 + height as i32;
+        for x in xs.start.max(columns.start)..xs.end.min(columns.end) {
+            for y in ys.start.max(rows.start)..ys.end.min(rows.end) {
+                self.draw_pixel(x as i16, y as i16, color);
+            }
+        }
+    }
+
+    /// Draws the outline of a circle.
+    pub fn draw_circle(&mut self, center: (i16, i16), radius: u16, color: Color) {
+        self.draw_ellipse(center, radius, radius, color);
+    }
+
+    /// Fills a circle.
+    pub fn fill_circle(&mut self, center: (i16, i16), radius: u16, color: Color) {
+        self.fill_ellipse(center, radius, radius, color);
+    }
+
+    /// Draws the outline of an axis-aligned ellipse with the horizontal radius `rx` and the
+    /// vertical radius `ry`.
+    pub fn draw_ellipse(&mut self, center: (i16, i16), rx: u16, ry: u16, color: Color) {
+        // Enough segments for neighbouring points to be at most a dot or two apart
+        let segments = ((rx as usize + ry as usize) * 4).max(8);
+        let points: Vec<(i16, i16)> = (0..=segments)
+            .map(|i| {
+                let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+                (
+                    saturate(center.0 as i32 + (rx as f32 * angle.cos()).round() as i32),
+                    saturate(center.1 as i32 + (ry as f32 * angle.sin()).round() as i32),
+                )
+            })
+            .collect();
+        self.draw_polyline(&points, color);
+    }
+
+    /// Fills an axis-aligned ellipse with the horizontal radius `rx` and the vertical radius
+    /// `ry`.
+    pub fn fill_ellipse(&mut self, center: (i16, i16), rx: u16, ry: u16, color: Color) {
+        let (columns, rows) = self.dot_ranges();
+        let (cx, cy, ry) = (center.0 as i32, center.1 as i32, ry as i32);
+        for y in (cy - ry).max(rows.start)..(cy + ry + 1).min(rows.end) {
+            let dy = y - cy;
+            let ratio = if ry == 0 { 0.0 } else { dy as f32 / ry as f32 };
+            let half_width = (rx as f32 * (1.0 - ratio * ratio).sqrt()).round() as i32;
+            for x in (cx - half_width).max(columns.start)..(cx + half_width + 1).min(columns.end) {
+                self.draw_pixel(x as i16, y as i16, color);
+            }
+        }
+    }
+
+    /// Fills a polygon given by its corners. The polygon is closed automatically, and may be
+    /// concave or self-intersecting, in which case the even-odd rule decides what is inside.
+    pub fn fill_polygon(&mut self, points: &[(i16, i16)], color: Color) {
+        if points.len() < 3 {
+            self.draw_polyline(points, color);
+            return;
+        }
+        let min_y = points
+            .iter()
+            .map(|p| p.1)
+            .min()
+            .expect("Points are not empty");
+        let max_y = points
+            .iter()
+            .map(|p| p.1)
+            .max()
+            .expect("Points are not empty");
+
+        // Only rows and spans on the screen are filled
+        let (columns, rows) = self.dot_ranges();
+        let rows = (min_y as i32).max(rows.start)..=(max_y as i32).min(rows.end - 1);
+        let mut crossings: Vec<f32> = Vec::with_capacity(points.len());
+        for y in rows {
+            let y = y as i16;
+            crossings.clear();
+            // Scanlines go through dot centers, edges include their lower end point only
+            let scan_y = y as f32;
+            for (i, &(x0, y0)) in points.iter().enumerate() {
+                let (x1, y1) = points[(i + 1) % points.len()];
+                let (y0, y1, x0, x1) = (y0 as f32, y1 as f32, x0 as f32, x1 as f32);
+                if (y0 <= scan_y && scan_y < y1) || (y1 <= scan_y && scan_y < y0) {
+                    crossings.push(x0 + (scan_y - y0) / (y1 - y0) * (x1 - x0));
+                }
+            }
+            crossings.sort_by(f32::total_cmp);
+            for span in crossings.chunks_exact(2) {
+                let start = (span[0].round() as i32).max(columns.start);
+                let end = (span[1].round() as i32).min(columns.end - 1);
+                for x in start..=end {
+                    self.draw_pixel(x as i16, y, color);
+                }
+            }
+        }
+        // The top-most row is excluded by the scanline rule, the outline covers it
+        let mut outline = points.to_vec();
+        outline.push(points[0]);
+        self.draw_polyline(&outline, color);
+    }
+
+    /// Draws a Bézier curve through the given control points. Two points give a straight line,
+    /// three a quadratic and four a cubic curve, and so on.
+    pub fn draw_bezier(&mut self, control_points: &[(i16, i16)], color: Color) {
+        if control_points.len() < 2 {
+            self.draw_polyline(control_points, color);
+            return;
+        }
+        let control_points: Vec<Point2<f32>> = control_points
+            .iter()
+            .map(|&(x, y)| Point2::new(x as f32, y as f32))
+            .collect();
+        // The curve is never longer than its control polygon
+        let length: f32 = control_points
+            .windows(2)
+            .map(|w| nalgebra::distance(&w[0], &w[1]))
+            .sum();
+        let segments = ((length / 2.0).ceil() as usize).max(1);
+
+        let mut scratch = Vec::with_capacity(control_points.len());
+        let points: Vec<(i16, i16)> = (0..=segments)
+            .map(|i| {
+                let t = i as f32 / segments as f32;
+                let point = de_casteljau(&control_points, t, &mut scratch);
+                (point.x.round() as i16, point.y.round() as i16)
+            })
+            .collect();
+        self.draw_polyline(&points, color);
+    }
+
+    /// Columns and rows of dots in the viewport.
+    fn dot_ranges(&self) -> (Range<i32>, Range<i32>) {
+        let (width, height) = self.viewport.size();
+        (0..width as i32, 0..height as i32)
+    }
+}
+
+/// Converts a coordinate to a dot, clamping it to the range of dots. Coordinates that far out
+/// are off the screen either way.
+fn saturate(value: i32) -> i16 {
+    value.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{viewport::Viewport, Color, Line, Renderer};
+
+    fn lit_dots(renderer: &Renderer) -> usize {
+        renderer
+            .coverage_buffer
+            .iter()
+            .flatten()
+            .filter(|&&covered| covered)
+            .count()
+    }
+
+    #[test]
+    fn test_line_length() {
+        for to in [(5, 2), (-3, 7), (0, -4), (6, 6), (0, 0)] {
+            let line = Line::new((0, 0), to);
+            assert_eq!(line.len(), line.count());
+            assert_eq!(Some(to), Line::new((0, 0), to).last());
+        }
+    }
+
+    #[test]
+    fn test_fill_rectangle() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+        renderer.fill_rectangle((2, 3), 4, 5, Color::rgb(255, 255, 255));
+        assert_eq!(20, lit_dots(&renderer));
+        assert_eq!(255, renderer.screen_buffer[5][7].0);
+    }
+
+    #[test]
+    fn test_fill_polygon_matches_rectangle() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+        renderer.fill_polygon(&[(2, 3), (5, 3), (5, 7), (2, 7)], Color::rgb(255, 255, 255));
+        assert_eq!(20, lit_dots(&renderer));
+    }
+
+    #[test]
+    fn test_far_off_screen_shapes() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+        renderer.draw_line((-20000, 0), (20000, 0), Color::WHITE);
+        assert_eq!(16, lit_dots(&renderer));
+
+        renderer.clear();
+        renderer.fill_rectangle((30000, 0), 5000, 1, Color::WHITE);
+        renderer.fill_rectangle((-30000, 0), u16::MAX, 2, Color::WHITE);
+        renderer.draw_rectangle((-100, -100), u16::MAX, u16::MAX, Color::WHITE);
+        assert_eq!(32, lit_dots(&renderer));
+
+        renderer.clear();
+        renderer.draw_circle((0, 0), 40000, Color::WHITE);
+        renderer.draw_bezier(&[(i16::MIN, 0), (i16::MAX, 0)], Color::WHITE);
+        assert_eq!(16, lit_dots(&renderer));
+
+        // Far away, but large enough to cover the whole viewport
+        renderer.clear();
+        renderer.fill_circle((i16::MAX, i16::MIN), u16::MAX, Color::WHITE);
+        assert_eq!(256, lit_dots(&renderer));
+
+        renderer.clear();
+        renderer.fill_polygon(
+            &[(-32000, -32000), (32000, -32000), (0, 32000)],
+            Color::WHITE,
+        );
+        assert_eq!(256, lit_dots(&renderer));
+    }
+}
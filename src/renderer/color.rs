@@ -0,0 +1,386 @@
+use std::{
+    error::Error,
+    fmt,
+    ops::{Add, Mul},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use nalgebra::Vector3;
+
+/// An sRGB colour with 8 bits per channel.
+///
+/// The channels are stored gamma encoded, the way terminals expect them. Blending operations, as
+/// well as adding colours together and multiplying them with each other or with a factor, convert
+/// to linear light first, so mixing colours does not darken them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Color(pub u8, pub u8, pub u8);
+
+impl Color {
+    pub const BLACK: Color = Color(0, 0, 0);
+    pub const WHITE: Color = Color(255, 255, 255);
+    pub const GRAY: Color = Color(128, 128, 128);
+    pub const RED: Color = Color(255, 0, 0);
+    pub const GREEN: Color = Color(0, 255, 0);
+    pub const BLUE: Color = Color(0, 0, 255);
+    pub const YELLOW: Color = Color(255, 255, 0);
+    pub const CYAN: Color = Color(0, 255, 255);
+    pub const MAGENTA: Color = Color(255, 0, 255);
+    pub const ORANGE: Color = Color(255, 165, 0);
+
+    /// Constructs a colour from red, green and blue components.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color(r, g, b)
+    }
+
+    /// Constructs a colour from a hex value such as `0xff8800`.
+    pub const fn from_hex(hex: u32) -> Color {
+        Color((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+    }
+
+    /// The colour as a hex value such as `0xff8800`.
+    pub const fn to_hex(self) -> u32 {
+        (self.0 as u32) << 16 | (self.1 as u32) << 8 | self.2 as u32
+    }
+
+    /// Constructs a colour from hue in degrees, and saturation and value between 0 and 1.
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Color {
+        let hue = hue.rem_euclid(360.0) / 60.0;
+        let (saturation, value) = (saturation.clamp(0.0, 1.0), value.clamp(0.0, 1.0));
+        let chroma = value * saturation;
+        let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+        let (r, g, b) = match hue as u8 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = value - chroma;
+        Color::from(Vector3::new(r + m, g + m, b + m))
+    }
+
+    /// The colour as hue in degrees, and saturation and value between 0 and 1.
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let channels = Vector3::from(self);
+        let (r, g, b) = (channels.x, channels.y, channels.z);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let chroma = max - min;
+        let hue = if chroma == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / chroma + 2.0)
+        } else {
+            60.0 * ((r - g) / chroma + 4.0)
+        };
+        let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+        (hue, saturation, max)
+    }
+
+    /// The colour in linear light, with channels between 0 and 1.
+    pub fn to_linear(self) -> Vector3<f32> {
+        let table = srgb_to_linear_table();
+        Vector3::new(
+            table[self.0 as usize],
+            table[self.1 as usize],
+            table[self.2 as usize],
+        )
+    }
+
+    /// Constructs a colour from linear light, with channels between 0 and 1.
+    pub fn from_linear(linear: Vector3<f32>) -> Color {
+        Color(
+            linear_to_srgb(linear.x),
+            linear_to_srgb(linear.y),
+            linear_to_srgb(linear.z),
+        )
+    }
+
+    /// Relative luminance of the colour in linear light, between 0 and 1.
+    pub fn luminance(self) -> f32 {
+        self.to_linear().dot(&Vector3::new(0.2126, 0.7152, 0.0722))
+    }
+
+    /// Linear interpolation towards `other`, with `t` going from 0 to 1.
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        Color::from_linear(self.to_linear().lerp(&other.to_linear(), t.clamp(0.0, 1.0)))
+    }
+
+    /// Composites the colour over `background`, with `alpha` as its opacity between 0 and 1.
+    pub fn over(self, background: Color, alpha: f32) -> Color {
+        background.lerp(self, alpha)
+    }
+
+    /// Average of the colours in linear light, or `None` if there are none.
+    pub fn mean(colors: impl IntoIterator<Item = Color>) -> Option<Color> {
+        let mut sum = Vector3::zeros();
+        let mut count = 0;
+        for color in colors {
+            sum += color.to_linear();
+            count += 1;
+        }
+        (count > 0).then(|| Color::from_linear(sum / count as f32))
+    }
+}
+
+/// Adds the light of two colours together, saturating at white.
+impl Add for Color {
+    type Output = Color;
+
+    fn add(self, other: Color) -> Color {
+        Color::from_linear(self.to_linear() + other.to_linear())
+    }
+}
+
+/// Multiplies the channels of two colours, like light filtered through a coloured surface.
+impl Mul for Color {
+    type Output = Color;
+
+    fn mul(self, other: Color) -> Color {
+        Color::from_linear(self.to_linear().component_mul(&other.to_linear()))
+    }
+}
+
+/// Scales the brightness of the colour in linear light.
+impl Mul<f32> for Color {
+    type Output = Color;
+
+    fn mul(self, factor: f32) -> Color {
+        Color::from_linear(self.to_linear() * factor.max(0.0))
+    }
+}
+
+/// Lookup table from the 256 sRGB channel values to linear light.
+fn srgb_to_linear_table() -> &'static [f32; 256] {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0.0; 256];
+        for (value, linear) in table.iter_mut().enumerate() {
+            let value = value as f32 / 255.0;
+            *linear = if value <= 0.04045 {
+                value / 12.92
+            } else {
+                ((value + 0.055) / 1.055).powf(2.4)
+            };
+        }
+        table
+    })
+}
+
+/// Gamma encodes a linear channel, using a lookup table with enough entries that every sRGB value
+/// is reachable.
+fn linear_to_srgb(linear: f32) -> u8 {
+    const STEPS: usize = 4096;
+    static TABLE: OnceLock<Vec<u8>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        (0..=STEPS)
+            .map(|i| {
+                let linear = i as f32 / STEPS as f32;
+                let value = if linear <= 0.003_130_8 {
+                    linear * 12.92
+                } else {
+                    1.055 * linear.powf(1.0 / 2.4) - 0.055
+                };
+                (value * 255.0).round() as u8
+            })
+            .collect()
+    });
+    table[(linear.clamp(0.0, 1.0) * STEPS as f32).round() as usize]
+}
+
+/// Converts gamma encoded channels between 0 and 1, clamping values outside that range.
+impl From<Vector3<f32>> for Color {
+    fn from(channels: Vector3<f32>) -> Self {
+        let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Color(
+            channel(channels.x),
+            channel(channels.y),
+            channel(channels.z),
+        )
+    }
+}
+
+/// Converts to gamma encoded channels between 0 and 1.
+impl From<Color> for Vector3<f32> {
+    fn from(color: Color) -> Self {
+        Vector3::new(color.0 as f32, color.1 as f32, color.2 as f32) / 255.0
+    }
+}
+
+impl From<Color> for crossterm::style::Color {
+    fn from(color: Color) -> Self {
+        crossterm::style::Color::Rgb {
+            r: color.0,
+            g: color.1,
+            b: color.2,
+        }
+    }
+}
+
+/// Converts named and 256-colour palette terminal colours using the usual xterm values. Fails for
+/// [`crossterm::style::Color::Reset`], which has no colour of its own.
+impl TryFrom<crossterm::style::Color> for Color {
+    type Error = ColorError;
+
+    fn try_from(color: crossterm::style::Color) -> Result<Self, Self::Error> {
+        use crossterm::style::Color as Terminal;
+        let ansi = match color {
+            Terminal::Reset => return Err(ColorError::NoColor),
+            Terminal::Rgb { r, g, b } => return Ok(Color(r, g, b)),
+            Terminal::AnsiValue(value) => value,
+            Terminal::Black => 0,
+            Terminal::DarkRed => 1,
+            Terminal::DarkGreen => 2,
+            Terminal::DarkYellow => 3,
+            Terminal::DarkBlue => 4,
+            Terminal::DarkMagenta => 5,
+            Terminal::DarkCyan => 6,
+            Terminal::Grey => 7,
+            Terminal::DarkGrey => 8,
+            Terminal::Red => 9,
+            Terminal::Green => 10,
+            Terminal::Yellow => 11,
+            Terminal::Blue => 12,
+            Terminal::Magenta => 13,
+            Terminal::Cyan => 14,
+            Terminal::White => 15,
+        };
+        Ok(Color::from_ansi(ansi))
+    }
+}
+
+impl Color {
+    /// Colour of an entry in the xterm 256-colour palette.
+    fn from_ansi(value: u8) -> Color {
+        const BASIC: [u32; 16] = [
+            0x000000, 0x800000, 0x008000, 0x808000, 0x000080, 0x800080, 0x008080, 0xc0c0c0,
+            0x808080, 0xff0000, 0x00ff00, 0xffff00, 0x0000ff, 0xff00ff, 0x00ffff, 0xffffff,
+        ];
+        const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+        match value {
+            0..=15 => Color::from_hex(BASIC[value as usize]),
+            16..=231 => {
+                let index = value - 16;
+                Color(
+                    CUBE_LEVELS[(index / 36) as usize],
+                    CUBE_LEVELS[(index / 6 % 6) as usize],
+                    CUBE_LEVELS[(index % 6) as usize],
+                )
+            }
+            232..=255 => {
+                let gray = 8 + (value - 232) * 10;
+                Color(gray, gray, gray)
+            }
+        }
+    }
+}
+
+/// Parses hex colours in the forms `#ff8800`, `ff8800`, `#f80` and `f80`.
+impl FromStr for Color {
+    type Err = ColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.strip_prefix('#').unwrap_or(s);
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ColorError::InvalidHex(s.to_string()));
+        }
+        match digits.len() {
+            6 => Ok(Color::from_hex(
+                u32::from_str_radix(digits, 16).expect("Digits are valid hex"),
+            )),
+            3 => {
+                let short = u32::from_str_radix(digits, 16).expect("Digits are valid hex");
+                let expand = |nibble: u32| (nibble & 0xf) as u8 * 0x11;
+                Ok(Color(expand(short >> 8), expand(short >> 4), expand(short)))
+            }
+            _ => Err(ColorError::InvalidHex(s.to_string())),
+        }
+    }
+}
+
+/// Error returned when a value can not be turned into a [`Color`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ColorError {
+    /// The string is not a hex colour.
+    InvalidHex(String),
+    /// The terminal colour does not stand for any particular colour.
+    NoColor,
+}
+
+impl fmt::Display for ColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorError::InvalidHex(s) => write!(f, "invalid hex colour: {s:?}"),
+            ColorError::NoColor => write!(f, "terminal colour has no RGB value"),
+        }
+    }
+}
+
+impl Error for ColorError {}
+
+#[cfg(test)]
+mod tests {
+    use super::Color;
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(Color(255, 136, 0), Color::from_hex(0xff8800));
+        assert_eq!(0xff8800, Color(255, 136, 0).to_hex());
+        assert_eq!(Ok(Color(255, 136, 0)), "#ff8800".parse());
+        assert_eq!(Ok(Color(255, 136, 0)), "f80".parse());
+        assert!("#ff880".parse::<Color>().is_err());
+        assert!("#gg8800".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn test_hsv_round_trip() {
+        for color in [Color::ORANGE, Color::CYAN, Color(12, 200, 90), Color::GRAY] {
+            let (h, s, v) = color.to_hsv();
+            assert_eq!(color, Color::from_hsv(h, s, v));
+        }
+        assert_eq!(Color::BLUE, Color::from_hsv(240.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_linear_round_trip() {
+        for value in 0..=255 {
+            let color = Color(value, value, value);
+            assert_eq!(color, Color::from_linear(color.to_linear()));
+        }
+    }
+
+    #[test]
+    fn test_blending_in_linear_light() {
+        // Half of white in linear light is brighter than half of the gamma encoded value
+        let mid = Color::BLACK.lerp(Color::WHITE, 0.5);
+        assert_eq!(Color(188, 188, 188), mid);
+        assert_eq!(mid, Color::WHITE.over(Color::BLACK, 0.5));
+        assert_eq!(Some(mid), Color::mean([Color::BLACK, Color::WHITE]));
+        assert_eq!(Color::RED, Color::WHITE * Color::RED);
+        assert_eq!(Color::YELLOW, Color::RED + Color::GREEN);
+        assert_eq!(mid, Color::WHITE * 0.5);
+    }
+
+    #[test]
+    fn test_terminal_colors() {
+        use crossterm::style::Color as Terminal;
+        assert_eq!(
+            Ok(Color(1, 2, 3)),
+            Color::try_from(Terminal::Rgb { r: 1, g: 2, b: 3 })
+        );
+        assert_eq!(Ok(Color(255, 0, 0)), Color::try_from(Terminal::Red));
+        assert_eq!(
+            Ok(Color(95, 135, 175)),
+            Color::try_from(Terminal::AnsiValue(67))
+        );
+        assert_eq!(
+            Ok(Color(238, 238, 238)),
+            Color::try_from(Terminal::AnsiValue(255))
+        );
+        assert!(Color::try_from(Terminal::Reset).is_err());
+    }
+}
@@ -0,0 +1,312 @@
+use std::sync::OnceLock;
+
+use super::Color;
+
+/// Algorithm for turning the brightness of the screen buffer into patterns of dots.
+///
+/// Without dithering every dot that has been drawn to is lit, and shading is only visible through
+/// the colour of the characters. With dithering, brighter areas get more dots, so shading
+/// survives on terminals without colours. Lit dots get the hue of the original colour at full
+/// brightness.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dithering {
+    /// Ordered dithering with a 4 by 4 Bayer matrix. Fast and stable between frames, but leaves a
+    /// visible cross-hatch pattern.
+    Bayer,
+    /// Ordered dithering with a blue noise mask. Stable between frames, with less structure than
+    /// [`Dithering::Bayer`].
+    BlueNoise,
+    /// Floyd–Steinberg error diffusion. Preserves the most detail, but patterns shift when the
+    /// image changes.
+    FloydSteinberg,
+    /// Atkinson error diffusion. Only diffuses part of the error, which gives more contrast than
+    /// [`Dithering::FloydSteinberg`].
+    Atkinson,
+}
+
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+const BLUE_NOISE_SIZE: usize = 16;
+
+/// Error diffusion kernels, as offsets to the right and down with their share of the error.
+const FLOYD_STEINBERG: [(isize, isize, f32); 4] = [
+    (1, 0, 7.0 / 16.0),
+    (-1, 1, 3.0 / 16.0),
+    (0, 1, 5.0 / 16.0),
+    (1, 1, 1.0 / 16.0),
+];
+
+/// Spreads an eighth of the error to each of six neighbours, dropping the remaining quarter.
+const ATKINSON: [(isize, isize, f32); 6] = [
+    (1, 0, 1.0 / 8.0),
+    (2, 0, 1.0 / 8.0),
+    (-1, 1, 1.0 / 8.0),
+    (0, 1, 1.0 / 8.0),
+    (1, 1, 1.0 / 8.0),
+    (0, 2, 1.0 / 8.0),
+];
+
+/// Applies dithering to a buffer of dots and its coverage, indexed by column and row. Dots that
+/// are not covered stay unlit.
+pub(crate) fn dither(dots: &mut [Vec<Color>], coverage: &mut [Vec<bool>], dithering: Dithering) {
+    let mut luminance: Vec<Vec<f32>> = dots
+        .iter()
+        .zip(coverage.iter())
+        .map(|(column, covered)| {
+            column
+                .iter()
+                .zip(covered)
+                .map(|(&dot, &covered)| if covered { dot.luminance() } else { 0.0 })
+                .collect()
+        })
+        .collect();
+
+    match dithering {
+        Dithering::Bayer => threshold(dots, coverage, &luminance, |x, y| {
+            (BAYER_4X4[y % 4][x % 4] as f32 + 0.5) / 16.0
+        }),
+        Dithering::BlueNoise => {
+            let mask = blue_noise();
+            threshold(dots, coverage, &luminance, |x, y| {
+                let rank = mask[(y % BLUE_NOISE_SIZE) * BLUE_NOISE_SIZE + x % BLUE_NOISE_SIZE];
+                (rank as f32 + 0.5) / (BLUE_NOISE_SIZE * BLUE_NOISE_SIZE) as f32
+            })
+        }
+        Dithering::FloydSteinberg => diffuse(dots, coverage, &mut luminance, &FLOYD_STEINBERG),
+        Dithering::Atkinson => diffuse(dots, coverage, &mut luminance, &ATKINSON),
+    }
+}
+
+/// Lights a dot at full brightness, or turns it off.
+fn set_lit(dot: &mut Color, covered: &mut bool, lit: bool) {
+    let brightest = dot.0.max(dot.1).max(dot.2);
+    *covered = lit && brightest > 0;
+    if *covered {
+        let scale = |channel: u8| (channel as u32 * 255 / brightest as u32) as u8;
+        *dot = Color(scale(dot.0), scale(dot.1), scale(dot.2));
+    }
+}
+
+/// Ordered dithering against a threshold map. Rows are counted from the top of the screen.
+fn threshold(
+    dots: &mut [Vec<Color>],
+    coverage: &mut [Vec<bool>],
+    luminance: &[Vec<f32>],
+    map: impl Fn(usize, usize) -> f32,
+) {
+    for (x, (column, covered)) in dots.iter_mut().zip(coverage.iter_mut()).enumerate() {
+        let height = column.len();
+        for (y, (dot, covered)) in column.iter_mut().zip(covered.iter_mut()).enumerate() {
+            set_lit(dot, covered, luminance[x][y] > map(x, height - 1 - y));
+        }
+    }
+}
+
+/// Error diffusion dithering, going through the rows from the top of the screen. Each kernel
+/// entry is an offset to the right and down, and its share of the error.
+fn diffuse(
+    dots: &mut [Vec<Color>],
+    coverage: &mut [Vec<bool>],
+    luminance: &mut [Vec<f32>],
+    kernel: &[(isize, isize, f32)],
+) {
+    let width = dots.len() as isize;
+    let height = dots.first().map_or(0, Vec::len) as isize;
+
+    for row in 0..height {
+        for x in 0..width {
+            let y = (height - 1 - row) as usize;
+            let value = luminance[x as usize][y];
+            let lit = value > 0.5;
+            set_lit(&mut dots[x as usize][y], &mut coverage[x as usize][y], lit);
+
+            let error = value - if lit { 1.0 } else { 0.0 };
+            for &(dx, drow, weight) in kernel {
+                let (nx, nrow) = (x + dx, row + drow);
+                if (0..width).contains(&nx) && (0..height).contains(&nrow) {
+                    let ny = (height - 1 - nrow) as usize;
+                    luminance[nx as usize][ny] += error * weight;
+                }
+            }
+        }
+    }
+}
+
+/// A tileable blue noise threshold map, ranking each cell of a square from 0 to its area. It is
+/// generated once with the void-and-cluster method.
+fn blue_noise() -> &'static [u16] {
+    static MASK: OnceLock<Vec<u16>> = OnceLock::new();
+    MASK.get_or_init(|| void_and_cluster(BLUE_NOISE_SIZE, 1.5))
+}
+
+/// Energy of every cell, as the sum of a Gaussian of the wrapping distance to every set cell.
+struct Energy {
+    size: usize,
+    kernel: Vec<f32>,
+    values: Vec<f32>,
+}
+
+impl Energy {
+    fn new(size: usize, sigma: f32) -> Energy {
+        let mut kernel = vec![0.0; size * size];
+        for dy in 0..size {
+            for dx in 0..size {
+                let wx = dx.min(size - dx) as f32;
+                let wy = dy.min(size - dy) as f32;
+                kernel[dy * size + dx] = (-(wx * wx + wy * wy) / (2.0 * sigma * sigma)).exp();
+            }
+        }
+        Energy {
+            size,
+            kernel,
+            values: vec![0.0; size * size],
+        }
+    }
+
+    /// Adds or removes the energy contributed by a set cell.
+    fn update(&mut self, cell: usize, sign: f32) {
+        let (cx, cy) = (cell % self.size, cell / self.size);
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let dx = (x + self.size - cx) % self.size;
+                let dy = (y + self.size - cy) % self.size;
+                self.values[y * self.size + x] += sign * self.kernel[dy * self.size + dx];
+            }
+        }
+    }
+
+    /// The set cell with the highest energy.
+    fn tightest_cluster(&self, pattern: &[bool]) -> usize {
+        (0..pattern.len())
+            .filter(|&i| pattern[i])
+            .max_by(|&a, &b| self.values[a].total_cmp(&self.values[b]))
+            .expect("Pattern has set cells")
+    }
+
+    /// The unset cell with the lowest energy.
+    fn largest_void(&self, pattern: &[bool]) -> usize {
+        (0..pattern.len())
+            .filter(|&i| !pattern[i])
+            .min_by(|&a, &b| self.values[a].total_cmp(&self.values[b]))
+            .expect("Pattern has unset cells")
+    }
+}
+
+fn void_and_cluster(size: usize, sigma: f32) -> Vec<u16> {
+    let area = size * size;
+
+    // Evenly spread initial pattern, from a fixed pseudo-random start
+    let mut pattern = vec![false; area];
+    let mut energy = Energy::new(size, sigma);
+    let mut seed: u32 = 0x9e37_79b9;
+    let mut initial = 0;
+    while initial < area / 10 {
+        seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let cell = (seed >> 8) as usize % area;
+        if !pattern[cell] {
+            pattern[cell] = true;
+            energy.update(cell, 1.0);
+            initial += 1;
+        }
+    }
+    loop {
+        let cluster = energy.tightest_cluster(&pattern);
+        pattern[cluster] = false;
+        energy.update(cluster, -1.0);
+        let void = energy.largest_void(&pattern);
+        if void == cluster {
+            pattern[cluster] = true;
+            energy.update(cluster, 1.0);
+            break;
+        }
+        pattern[void] = true;
+        energy.update(void, 1.0);
+    }
+
+    let mut ranks = vec![0u16; area];
+
+    // Rank the initial points by removing the most clustered ones first
+    let mut removing = pattern.clone();
+    let mut removing_energy = Energy::new(size, sigma);
+    for cell in (0..area).filter(|&i| removing[i]) {
+        removing_energy.update(cell, 1.0);
+    }
+    for rank in (0..initial).rev() {
+        let cluster = removing_energy.tightest_cluster(&removing);
+        removing[cluster] = false;
+        removing_energy.update(cluster, -1.0);
+        ranks[cluster] = rank as u16;
+    }
+
+    // Rank the rest by filling the largest voids first
+    for rank in initial..area {
+        let void = energy.largest_void(&pattern);
+        pattern[void] = true;
+        energy.update(void, 1.0);
+        ranks[void] = rank as u16;
+    }
+
+    ranks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        blue_noise, diffuse, dither, Dithering, ATKINSON, BLUE_NOISE_SIZE, FLOYD_STEINBERG,
+    };
+    use crate::renderer::Color;
+    use nalgebra::Vector3;
+
+    fn lit_fraction(dithering: Dithering, luminance: f32) -> f32 {
+        let gray = Color::from_linear(Vector3::repeat(luminance));
+        let mut dots = vec![vec![gray; 16]; 16];
+        let mut coverage = vec![vec![true; 16]; 16];
+        dither(&mut dots, &mut coverage, dithering);
+        let lit = coverage
+            .iter()
+            .flatten()
+            .filter(|&&covered| covered)
+            .count();
+        lit as f32 / 256.0
+    }
+
+    #[test]
+    fn test_dot_density_follows_luminance() {
+        for dithering in [
+            Dithering::Bayer,
+            Dithering::BlueNoise,
+            Dithering::FloydSteinberg,
+        ] {
+            assert_eq!(0.0, lit_fraction(dithering, 0.0));
+            assert!((lit_fraction(dithering, 0.25) - 0.25).abs() < 0.05);
+            assert!((lit_fraction(dithering, 0.75) - 0.75).abs() < 0.05);
+            assert_eq!(1.0, lit_fraction(dithering, 1.0));
+        }
+    }
+
+    #[test]
+    fn test_atkinson_drops_a_quarter_of_the_error() {
+        let share = |kernel: &[(isize, isize, f32)]| kernel.iter().map(|&(_, _, w)| w).sum::<f32>();
+        assert_eq!(1.0, share(&FLOYD_STEINBERG));
+        assert!(ATKINSON.iter().all(|&(_, _, weight)| weight == 0.125));
+        assert_eq!(0.75, share(&ATKINSON));
+
+        // The unlit top left dot passes an eighth of its brightness to the dot on its right,
+        // which gets nothing from anywhere else
+        let mut dots = vec![vec![Color::BLACK; 4]; 4];
+        let mut coverage = vec![vec![true; 4]; 4];
+        let mut luminance = vec![vec![0.0; 4]; 4];
+        luminance[0][3] = 0.4;
+        diffuse(&mut dots, &mut coverage, &mut luminance, &ATKINSON);
+        assert_eq!(0.05, luminance[1][3]);
+        assert!(coverage.iter().flatten().all(|&covered| !covered));
+    }
+
+    #[test]
+    fn test_blue_noise_ranks_are_unique() {
+        let mut ranks = blue_noise().to_vec();
+        ranks.sort();
+        let expected: Vec<u16> = (0..(BLUE_NOISE_SIZE * BLUE_NOISE_SIZE) as u16).collect();
+        assert_eq!(expected, ranks);
+    }
+}
@@ -0,0 +1,320 @@
+use nalgebra::{Point3, Vector3};
+
+use super::{camera::Camera, model::Model, Color, LineStyle, Renderer, Winding};
+
+/// Grid lines drawn on each side of the camera. The grid fades out towards its edge, so it looks
+/// like it goes on forever.
+const GRID_LINES: i32 = 32;
+
+/// Length of normal whiskers, relative to the radius of the model's bounding sphere.
+const NORMAL_LENGTH: f32 = 0.1;
+
+/// Pairs of corners of a box or frustum that are connected by an edge. Corners are numbered by
+/// their bits, x being the lowest.
+const BOX_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+/// Debug overlays drawn by the renderer, see [`Renderer::set_gizmos`]. All of them are off by
+/// default.
+#[derive(Clone, Copy, Debug)]
+pub struct Gizmos {
+    /// Draws the world X, Y and Z axes from the origin in red, green and blue.
+    pub axes: bool,
+    /// Draws a grid on the ground, the plane where y is [`grid_height`](#structfield.grid_height).
+    pub grid: bool,
+    /// Outlines the bounding box of every model.
+    pub bounding_boxes: bool,
+    /// Draws a short line from the centre of every face in the direction it faces.
+    pub normals: bool,
+    /// Draws the outline of what the camera can see.
+    pub frustum: Option<Camera>,
+    /// Length of the axes.
+    pub axis_length: f32,
+    /// Distance between grid lines.
+    pub grid_spacing: f32,
+    /// Height of the ground.
+    pub grid_height: f32,
+}
+
+impl Default for Gizmos {
+    fn default() -> Self {
+        Gizmos {
+            axes: false,
+            grid: false,
+            bounding_boxes: false,
+            normals: false,
+            frustum: None,
+            axis_length: 1.0,
+            grid_spacing: 1.0,
+            grid_height: 0.0,
+        }
+    }
+}
+
+impl Renderer {
+    /// Sets which debug overlays are drawn. Bounding boxes and normals are drawn with each model,
+    /// the others when rendering.
+    pub fn set_gizmos(&mut self, gizmos: Gizmos) {
+        self.gizmos = gizmos;
+    }
+
+    /// Outlines the bounding box of the model, in world space.
+    pub fn draw_bounding_box(&mut self, model: &Model, style: LineStyle) {
+        let Some(aabb) = model.bounding_box() else {
+            return;
+        };
+        let model_matrix = model.model_matrix();
+        let corners: [Point3<f32>; 8] = std::array::from_fn(|i| {
+            let pick = |bit, min: f32, max: f32| if i & bit == 0 { min } else { max };
+            model_matrix
+                * Point3::new(
+                    pick(1, aabb.min.x, aabb.max.x),
+                    pick(2, aabb.min.y, aabb.max.y),
+                    pick(4, aabb.min.z, aabb.max.z),
+                )
+        });
+        self.draw_box_edges(&corners, style);
+    }
+
+    /// Draws a line from the centre of each face of the model in the direction it faces. The
+    /// lines are a tenth as long as the radius of the model's bounding sphere.
+    pub fn draw_normals(&mut self, model: &Model, style: LineStyle) {
+        let Some(sphere) = model.bounding_sphere() else {
+            return;
+        };
+        let length = match self.front_face {
+            Winding::CounterClockwise => sphere.radius * NORMAL_LENGTH,
+            Winding::Clockwise => -sphere.radius * NORMAL_LENGTH,
+        };
+        let model_matrix = model.model_matrix();
+        for face in &model.index_buffer {
+            let Some(vertices) = face.vertices(&model.vertex_buffer) else {
+                continue;
+            };
+            let [p0, p1, p2] = vertices.map(|vertex| model_matrix * vertex.position);
+            let normal = (p1 - p0).cross(&(p2 - p0));
+            let Some(normal) = normal.try_normalize(f32::EPSILON) else {
+                continue;
+            };
+            let center = Point3::from((p0.coords + p1.coords + p2.coords) / 3.0);
+            self.draw_line_3d(center, center + normal * length, style);
+        }
+    }
+
+    /// Outlines the part of the world the camera can see, between its near and far planes.
+    pub fn draw_frustum(&mut self, camera: &Camera, style: LineStyle) {
+        let corners: [Point3<f32>; 8] = std::array::from_fn(|i| {
+            let pick = |bit| if i & bit == 0 { -1.0 } else { 1.0 };
+            let view = camera
+                .projection
+                .unproject_point(&Point3::new(pick(1), pick(2), pick(4)));
+            camera.view_matrix.inverse_transform_point(&view)
+        });
+        self.draw_box_edges(&corners, style);
+    }
+
+    /// Draws the world axes from the origin, X in red, Y in green and Z in blue.
+    pub fn draw_axes(&mut self, length: f32) {
+        let origin = Point3::origin();
+        for (axis, color) in [
+            (Vector3::x(), Color::RED),
+            (Vector3::y(), Color::GREEN),
+            (Vector3::z(), Color::BLUE),
+        ] {
+            let style = LineStyle {
+                color,
+                ..LineStyle::default()
+            };
+            self.draw_line_3d(origin, origin + axis * length, style);
+        }
+    }
+
+    /// Draws a grid on the plane where y is `height`, centred below the camera. The lines fade
+    /// into the background with their distance from the camera.
+    pub fn draw_grid(&mut self, height: f32, spacing: f32, color: Color) {
+        if spacing <= 0.0 {
+            return;
+        }
+        let eye = self.camera.view_matrix.inverse().translation.vector;
+        let [center_x, center_z] = [eye.x, eye.z].map(|c| (c / spacing).round() as i32);
+        let background = self.clear_color.unwrap_or(Color::BLACK);
+        let extent = GRID_LINES as f32 * spacing;
+        let point = |x: i32, z: i32| {
+            Point3::new(
+                (center_x + x) as f32 * spacing,
+                height,
+                (center_z + z) as f32 * spacing,
+            )
+        };
+
+        // Each line is drawn a cell at a time, so that it can fade out
+        for line in -GRID_LINES..=GRID_LINES {
+            for cell in -GRID_LINES..GRID_LINES {
+                for (from, to) in [
+                    (point(line, cell), point(line, cell + 1)),
+                    (point(cell, line), point(cell + 1, line)),
+                ] {
+                    let middle = nalgebra::center(&from, &to);
+                    let distance = (middle.coords.xz() - eye.xz()).norm();
+                    if distance >= extent {
+                        continue;
+                    }
+                    let style = LineStyle {
+                        color: color.lerp(background, distance / extent),
+                        ..LineStyle::default()
+                    };
+                    self.draw_line_3d(from, to, style);
+                }
+            }
+        }
+    }
+
+    /// Draws the overlays that belong to the model, after the model itself.
+    pub(super) fn draw_model_gizmos(&mut self, model: &Model) {
+        if self.gizmos.bounding_boxes {
+            let style = LineStyle {
+                color: Color::YELLOW,
+                ..LineStyle::default()
+            };
+            self.draw_bounding_box(model, style);
+        }
+        if self.gizmos.normals {
+            let style = LineStyle {
+                color: Color::CYAN,
+                ..LineStyle::default()
+            };
+            self.draw_normals(model, style);
+        }
+    }
+
+    /// Draws the overlays that belong to the world, before rendering.
+    pub(super) fn draw_world_gizmos(&mut self) {
+        let gizmos = self.gizmos;
+        if gizmos.grid {
+            self.draw_grid(gizmos.grid_height, gizmos.grid_spacing, Color::GRAY);
+        }
+        if gizmos.axes {
+            self.draw_axes(gizmos.axis_length);
+        }
+        if let Some(camera) = &gizmos.frustum {
+            let style = LineStyle {
+                color: Color::MAGENTA,
+                ..LineStyle::default()
+            };
+            self.draw_frustum(camera, style);
+        }
+    }
+
+    fn draw_box_edges(&mut self, corners: &[Point3<f32>; 8], style: LineStyle) {
+        for (a, b) in BOX_EDGES {
+            self.draw_line_3d(corners[a], corners[b], style);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        camera::Camera, model::Model, viewport::Viewport, Color, Face, Renderer, Vertex,
+    };
+    use super::Gizmos;
+    use nalgebra::{Isometry3, Point3};
+
+    fn has_color(renderer: &Renderer, color: Color) -> bool {
+        renderer.screen_buffer.iter().flatten().any(|&c| c == color)
+    }
+
+    fn triangle() -> Model {
+        let vertices = [(-1.0, -1.0), (1.0, -1.0), (0.0, 1.0)]
+            .map(|(x, y)| Vertex::new(Point3::new(x, y, 0.0)))
+            .to_vec();
+        let mut model = Model::from_buffers(vertices, vec![Face::new(0, 1, 2)]).unwrap();
+        model.translate(0.5, 0.5, -5.0);
+        model
+    }
+
+    #[test]
+    fn test_gizmos_are_off_by_default() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(16, 8, 0, 0));
+        renderer.draw_object(&triangle());
+        renderer.draw_world_gizmos();
+        assert!(!has_color(&renderer, Color::YELLOW));
+        assert!(!has_color(&renderer, Color::CYAN));
+    }
+
+    #[test]
+    fn test_model_gizmos() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(16, 8, 0, 0));
+        renderer.set_gizmos(Gizmos {
+            bounding_boxes: true,
+            normals: true,
+            ..Gizmos::default()
+        });
+        renderer.draw_object(&triangle());
+        assert!(has_color(&renderer, Color::YELLOW));
+        // The whisker points at the camera, in front of the face
+        assert!(has_color(&renderer, Color::CYAN));
+    }
+
+    #[test]
+    fn test_grid_is_hidden_behind_faces() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(16, 8, 0, 0));
+        renderer.set_gizmos(Gizmos {
+            grid: true,
+            grid_height: -1.0,
+            ..Gizmos::default()
+        });
+        renderer.draw_world_gizmos();
+        assert!(renderer.coverage_buffer.iter().flatten().any(|&c| c));
+
+        // A wall right in front of the camera hides the whole grid
+        renderer.clear();
+        let wall = |x, y| Vertex::new(Point3::new(x, y, -1.0));
+        let (width, height) = (32.0, 32.0);
+        renderer.draw_triangle(
+            &wall(0.0, 0.0),
+            &wall(width, 0.0),
+            &wall(0.0, height),
+            Some([Color::BLUE; 3]),
+            None,
+        );
+        renderer.draw_triangle(
+            &wall(width, 0.0),
+            &wall(width, height),
+            &wall(0.0, height),
+            Some([Color::BLUE; 3]),
+            None,
+        );
+        renderer.draw_world_gizmos();
+        assert!(renderer
+            .screen_buffer
+            .iter()
+            .flatten()
+            .all(|&c| c == Color::BLUE));
+    }
+
+    #[test]
+    fn test_frustum_of_another_camera() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(16, 8, 0, 0));
+        let mut camera = Camera::new(1.0, 1.0, 1.0, 3.0);
+        camera.view_matrix = Isometry3::translation(0.0, 0.0, 10.0);
+        renderer.set_gizmos(Gizmos {
+            frustum: Some(camera),
+            ..Gizmos::default()
+        });
+        renderer.draw_world_gizmos();
+        assert!(has_color(&renderer, Color::MAGENTA));
+    }
+}
@@ -0,0 +1,181 @@
+use nalgebra::{Matrix4, Point3, Vector4};
+
+use super::{bezier::de_casteljau, LineStyle, Renderer, Vertex};
+
+/// Most segments a curve is split into.
+const MAX_CURVE_SEGMENTS: usize = 1024;
+
+/// Segments of curves whose length on the screen is unknown, because they reach behind the
+/// camera.
+const DEFAULT_CURVE_SEGMENTS: usize = 64;
+
+impl Renderer {
+    /// Draws a straight line between two points in world space. See [`draw_polyline_3d`].
+    ///
+    /// [`draw_polyline_3d`]: #method.draw_polyline_3d
+    pub fn draw_line_3d(&mut self, from: Point3<f32>, to: Point3<f32>, style: LineStyle) {
+        self.draw_polyline_3d(&[from, to], style);
+    }
+
+    /// Draws lines connecting each point in world space to the next one, as seen by the camera.
+    /// Parts outside the camera's view are clipped, and parts behind already drawn faces are
+    /// hidden. Lines do not hide faces drawn after them.
+    pub fn draw_polyline_3d(&mut self, points: &[Point3<f32>], style: LineStyle) {
+        let vp_matrix = self.view_projection();
+        for segment in points.windows(2) {
+            let [from, to] = [segment[0], segment[1]].map(|p| vp_matrix * p.to_homogeneous());
+            self.draw_clipped_edge(from, to, style, true);
+        }
+    }
+
+    /// Draws a Bézier curve through control points in world space, like [`draw_bezier`] does on
+    /// the screen. The curve is split into straight lines about two dots long, see
+    /// [`draw_polyline_3d`].
+    ///
+    /// [`draw_bezier`]: #method.draw_bezier
+    /// [`draw_polyline_3d`]: #method.draw_polyline_3d
+    pub fn draw_bezier_3d(&mut self, control_points: &[Point3<f32>], style: LineStyle) {
+        if control_points.len() < 2 {
+            return;
+        }
+        // The curve is never longer than its control polygon, also on the screen
+        let vp_matrix = self.view_projection();
+        let (width, height) = self.buffer_size();
+        let screen = |p: &Point3<f32>| {
+            let clip = vp_matrix * p.to_homogeneous();
+            (clip.w > 0.0).then(|| {
+                let mut vertex = Vertex::new(Point3::from(clip.xyz() / clip.w));
+                vertex.map_to_screen(width as f32, height as f32);
+                vertex.position.xy()
+            })
+        };
+        let projected: Option<Vec<_>> = control_points.iter().map(screen).collect();
+        let segments = projected.map_or(DEFAULT_CURVE_SEGMENTS, |points| {
+            let length: f32 = points
+                .windows(2)
+                .map(|w| nalgebra::distance(&w[0], &w[1]))
+                .sum();
+            ((length / 2.0).ceil() as usize).clamp(1, MAX_CURVE_SEGMENTS)
+        });
+
+        let mut scratch = Vec::with_capacity(control_points.len());
+        let points: Vec<Point3<f32>> = (0..=segments)
+            .map(|i| de_casteljau(control_points, i as f32 / segments as f32, &mut scratch))
+            .collect();
+        self.draw_polyline_3d(&points, style);
+    }
+
+    /// Draws a line between two points in clip space, clipped to the view frustum before it is
+    /// mapped to the screen.
+    pub(super) fn draw_clipped_edge(
+        &mut self,
+        from: Vector4<f32>,
+        to: Vector4<f32>,
+        style: LineStyle,
+        depth_test: bool,
+    ) {
+        let Some((from, to)) = clip_line(from, to) else {
+            return;
+        };
+        let (width, height) = self.buffer_size();
+        let [v1, v2] = [from, to].map(|clip| {
+            let mut vertex = Vertex::new(Point3::from(clip.xyz() / clip.w));
+            vertex.map_to_screen(width as f32, height as f32);
+            vertex
+        });
+        Self::draw_edge(self, &v1, &v2, style, depth_test);
+    }
+
+    fn view_projection(&self) -> Matrix4<f32> {
+        self.camera.projection.as_matrix() * self.camera.view_matrix.to_homogeneous()
+    }
+}
+
+/// Clips a line in clip space to the view frustum, returning the visible part if there is one.
+fn clip_line(from: Vector4<f32>, to: Vector4<f32>) -> Option<(Vector4<f32>, Vector4<f32>)> {
+    // Distances to the six planes of the frustum, positive inside
+    let distances = |p: Vector4<f32>| {
+        [
+            p.w + p.x,
+            p.w - p.x,
+            p.w + p.y,
+            p.w - p.y,
+            p.w + p.z,
+            p.w - p.z,
+        ]
+    };
+    let (mut start, mut end) = (0.0f32, 1.0f32);
+    for (d0, d1) in distances(from).into_iter().zip(distances(to)) {
+        if d0 < 0.0 && d1 < 0.0 {
+            return None;
+        } else if d0 < 0.0 {
+            start = start.max(d0 / (d0 - d1));
+        } else if d1 < 0.0 {
+            end = end.min(d0 / (d0 - d1));
+        }
+    }
+    (start <= end).then(|| (from.lerp(&to, start), from.lerp(&to, end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{viewport::Viewport, Color, LineStyle, Renderer, Vertex};
+    use nalgebra::Point3;
+
+    const RED: LineStyle = LineStyle {
+        color: Color::RED,
+        thickness: 1,
+    };
+
+    #[test]
+    fn test_line_is_clipped() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+        // Crosses the near plane and ends behind the camera
+        renderer.draw_line_3d(Point3::new(0.0, 0.0, -5.0), Point3::new(0.0, 0.0, 5.0), RED);
+        assert_eq!(Color::RED, renderer.screen_buffer[8][8]);
+
+        renderer.clear();
+        renderer.draw_line_3d(
+            Point3::new(0.0, 10.0, 5.0),
+            Point3::new(0.0, -10.0, 5.0),
+            RED,
+        );
+        assert!(!renderer.coverage_buffer.iter().flatten().any(|&c| c));
+    }
+
+    #[test]
+    fn test_line_is_hidden_behind_faces() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+        let wall = |x, y| Vertex::new(Point3::new(x, y, 0.1));
+        renderer.draw_triangle(
+            &wall(0.0, 0.0),
+            &wall(8.0, 0.0),
+            &wall(0.0, 8.0),
+            Some([Color::BLUE; 3]),
+            None,
+        );
+        // Runs across the bottom of the screen, from outside its left edge
+        renderer.draw_polyline_3d(
+            &[
+                Point3::new(-15.0, -1.0, -5.0),
+                Point3::new(10.0, -1.0, -5.0),
+            ],
+            RED,
+        );
+        assert_eq!(Color::BLUE, renderer.screen_buffer[2][4]);
+        assert_eq!(Color::RED, renderer.screen_buffer[10][4]);
+    }
+
+    #[test]
+    fn test_bezier_passes_through_end_points() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(8, 4, 0, 0));
+        let control_points = [
+            Point3::new(0.0, 0.0, -5.0),
+            Point3::new(2.0, 2.0, -5.0),
+            Point3::new(0.0, 2.0, -5.0),
+        ];
+        renderer.draw_bezier_3d(&control_points, RED);
+        assert_eq!(Color::RED, renderer.screen_buffer[8][8]);
+        assert_eq!(Color::RED, renderer.screen_buffer[8][14]);
+    }
+}
@@ -0,0 +1,104 @@
+use super::Color;
+
+/// How a transparent colour is combined with what has already been drawn behind it. All modes
+/// work in linear light.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Covers the background according to the opacity, like coloured glass.
+    #[default]
+    Alpha,
+    /// Adds light to the background, scaled by the opacity. Useful for glows and highlights.
+    Additive,
+    /// Filters the background through the colour, scaled by the opacity.
+    Multiply,
+}
+
+impl BlendMode {
+    /// Blends `source` onto `destination`, with `alpha` as the opacity of the source between 0
+    /// and 1.
+    pub fn blend(self, source: Color, destination: Color, alpha: f32) -> Color {
+        let alpha = alpha.clamp(0.0, 1.0);
+        match self {
+            BlendMode::Alpha => source.over(destination, alpha),
+            BlendMode::Additive => destination + source * alpha,
+            BlendMode::Multiply => destination.lerp(destination * source, alpha),
+        }
+    }
+}
+
+/// Surface properties of a [`Model`].
+///
+/// The colour tints the vertex and face colours of the model. Materials that are not fully opaque
+/// or that use a blend mode other than [`BlendMode::Alpha`] are transparent, and are drawn after
+/// all opaque geometry.
+///
+/// [`Model`]: super::model::Model
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Material {
+    pub color: Color,
+    /// Opacity between 0 and 1.
+    pub alpha: f32,
+    pub blend_mode: BlendMode,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            color: Color::WHITE,
+            alpha: 1.0,
+            blend_mode: BlendMode::Alpha,
+        }
+    }
+}
+
+impl Material {
+    /// An opaque material of the given colour.
+    pub fn new(color: Color) -> Material {
+        Material {
+            color,
+            ..Material::default()
+        }
+    }
+
+    /// A material of the given colour and opacity, blended with [`BlendMode::Alpha`].
+    pub fn transparent(color: Color, alpha: f32) -> Material {
+        Material {
+            color,
+            alpha,
+            ..Material::default()
+        }
+    }
+
+    /// Whether the material has to be blended with what is behind it.
+    pub fn is_transparent(&self) -> bool {
+        self.alpha < 1.0 || self.blend_mode != BlendMode::Alpha
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlendMode, Material};
+    use crate::renderer::Color;
+
+    #[test]
+    fn test_blend_modes() {
+        let red = Color::RED;
+        let blue = Color::BLUE;
+        assert_eq!(red, BlendMode::Alpha.blend(red, blue, 1.0));
+        assert_eq!(blue, BlendMode::Alpha.blend(red, blue, 0.0));
+        assert_eq!(Color::MAGENTA, BlendMode::Additive.blend(red, blue, 1.0));
+        assert_eq!(Color::BLACK, BlendMode::Multiply.blend(red, blue, 1.0));
+        assert_eq!(red, BlendMode::Multiply.blend(Color::WHITE, red, 1.0));
+    }
+
+    #[test]
+    fn test_transparency() {
+        assert!(!Material::new(Color::RED).is_transparent());
+        assert!(Material::transparent(Color::RED, 0.5).is_transparent());
+        let additive = Material {
+            blend_mode: BlendMode::Additive,
+            ..Material::default()
+        };
+        assert!(additive.is_transparent());
+    }
+}
@@ -1,50 +1,188 @@
-use crate::renderer::{Face, Vertex};
-use std::{error::Error, fs};
+use crate::renderer::{
+    material::Material, CullMode, Face, LineStyle, PointStyle, RenderMode, Shading, Vertex,
+};
+use std::{
+    error::Error,
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader, Seek},
+};
 
-use nalgebra::{Isometry3, Point3, Translation3, UnitQuaternion};
+use nalgebra::{Isometry3, Point2, Point3, Translation3, UnitQuaternion};
+
+mod mesh;
+mod obj;
+mod ply;
+mod resolver;
+mod simplify;
+mod stl;
+mod subdivide;
+
+pub use mesh::{Aabb, BoundingSphere};
+pub use resolver::{FileResolver, NoResolver, Resolver};
+pub use simplify::Lod;
+pub use stl::StlFormat;
+
+/// Size of the buffer files are read through.
+const READ_BUFFER_SIZE: usize = 1 << 16;
 
 /// Struct that manages individual 3D objects.
 ///
 /// Models are represented as index and vertex buffers. Stores also the transformation data of the
 /// object.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Model {
     pub vertex_buffer: Vec<Vertex>,
     pub index_buffer: Vec<Face>,
     pub position: Translation3<f32>,
     pub rotation: UnitQuaternion<f32>,
+    /// Overrides the renderer's cull mode for this model when set. Useful for open meshes whose
+    /// interiors should stay visible.
+    pub cull_mode: Option<CullMode>,
+    /// Overrides the renderer's render mode for this model when set.
+    pub render_mode: Option<RenderMode>,
+    /// Overrides the renderer's line style for this model when set.
+    pub line_style: Option<LineStyle>,
+    /// Overrides the renderer's point style for this model when set.
+    pub point_style: Option<PointStyle>,
+    /// How vertex colours are applied across faces. Faces with a colour of their own are always
+    /// drawn with it.
+    pub shading: Shading,
+    /// Colour and transparency of the whole model. Without one, the model is opaque and keeps the
+    /// colours of its faces and vertices.
+    pub material: Option<Material>,
+    /// Faces as they were loaded, before being split into triangles. Empty for models made of
+    /// triangles only. Read through [`Model::polygons`], which checks that they still match the
+    /// index buffer.
+    polygons: Vec<Vec<usize>>,
+    /// Simplified versions of the model, from the most to the least detailed. The renderer picks
+    /// one based on how large the model is on the screen.
+    ///
+    /// Methods that change the mesh, such as [`Model::weld`] or [`Model::subdivide_loop`], clear
+    /// these, so that the old shape is not drawn at a distance. Call [`Model::generate_lods`]
+    /// again afterwards. Editing the buffers directly does not clear them.
+    pub lods: Vec<Lod>,
 }
 
 impl Model {
-    /// Loads and initializes a model from an .obj file wrapped in a Result.
+    /// Loads and initializes a model from an .obj, .stl or .ply file wrapped in a Result. Files
+    /// the model refers to, like OBJ material libraries, are looked up next to it. See
+    /// [`Model::from_bytes`].
     ///
     /// **This is mostly unfinished and incorrect, loads some files OK.**
     pub fn load_from_file(filename: &str) -> Result<Model, Box<dyn Error>> {
-        let mut vertex_buffer: Vec<Vertex> = vec![];
-        let mut index_buffer: Vec<Face> = vec![];
-        let obj_file = fs::read_to_string(filename)?;
+        Model::load_from_file_with_progress(filename, |_| {})
+    }
+
+    /// Loads a model like [`Model::load_from_file`], calling `progress` now and then while the
+    /// file is read, and once it has been read completely.
+    ///
+    /// OBJ files are parsed as they are read, after a quick first pass that counts their vertices
+    /// and faces so that the buffers are allocated only once. Other formats are read into memory
+    /// first.
+    pub fn load_from_file_with_progress(
+        filename: &str,
+        mut progress: impl FnMut(LoadProgress),
+    ) -> Result<Model, Box<dyn Error>> {
+        let mut file = BufReader::with_capacity(READ_BUFFER_SIZE, File::open(filename)?);
+        let total_bytes = file.get_ref().metadata()?.len();
+        let resolver = FileResolver::for_file(filename);
+        let mut report = |bytes_read| {
+            progress(LoadProgress {
+                bytes_read,
+                total_bytes,
+            })
+        };
 
-        for line in obj_file.lines() {
-            let values: Vec<&str> = line.split_whitespace().collect();
-            if values.is_empty() {
-                continue;
+        if !obj::is_obj(file.fill_buf()?) {
+            let mut data = Vec::with_capacity(total_bytes as usize);
+            loop {
+                let chunk = file.fill_buf()?;
+                if chunk.is_empty() {
+                    break;
+                }
+                data.extend_from_slice(chunk);
+                let length = chunk.len();
+                file.consume(length);
+                report(data.len() as u64);
             }
-            if values[0] == "v" {
-                // vertex data
-                let vertex = Vertex {
-                    position: Point3::new(
-                        values[1].parse()?,
-                        values[2].parse()?,
-                        values[3].parse()?,
-                    ),
-                };
-                vertex_buffer.push(vertex);
-            } else if values[0] == "f" {
-                // index data
-                let face = Face {
-                    indexes: (values[1].parse()?, values[2].parse()?, values[3].parse()?),
-                };
-                index_buffer.push(face);
+            return Model::from_bytes(&data, &resolver);
+        }
+
+        let counts = obj::count_elements(&mut file)?;
+        file.rewind()?;
+        obj::parse_obj(file, &resolver, counts, &mut report)
+    }
+
+    /// Loads a model from everything the reader returns. OBJ files are parsed as they are read,
+    /// other formats are read into memory first. See [`Model::from_bytes`].
+    pub fn from_reader(
+        mut reader: impl BufRead,
+        resolver: &dyn Resolver,
+    ) -> Result<Model, Box<dyn Error>> {
+        if obj::is_obj(reader.fill_buf()?) {
+            return obj::parse_obj(reader, resolver, Default::default(), &mut |_| {});
+        }
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        Model::from_bytes(&data, resolver)
+    }
+
+    /// Parses an .obj, .stl or .ply file from memory. The format is detected from the contents of
+    /// the data. See [`Model::from_obj`], [`Model::from_stl`] and [`Model::from_ply`] for the
+    /// formats, and [`Resolver`] for how referenced files are found.
+    pub fn from_bytes(data: &[u8], resolver: &dyn Resolver) -> Result<Model, Box<dyn Error>> {
+        if ply::is_ply(data) {
+            return Ok(Model::from_ply(data)?);
+        }
+        if stl::is_stl(data) {
+            return Ok(Model::from_stl(data)?);
+        }
+        Model::from_obj(std::str::from_utf8(data)?, resolver)
+    }
+
+    /// Constructs a model from its vertices and polygons of any number of vertices, which are
+    /// split into fans of triangles for drawing. The polygons are kept, see [`Model::polygons`].
+    pub fn from_polygons(
+        vertex_buffer: Vec<Vertex>,
+        polygons: Vec<Vec<usize>>,
+    ) -> Result<Model, ModelError> {
+        let vertex_count = vertex_buffer.len();
+        let mut index_buffer = vec![];
+        for (face, polygon) in polygons.iter().enumerate() {
+            if polygon.len() < 3 {
+                return Err(ModelError::DegeneratePolygon { face });
+            }
+            if let Some(&index) = polygon.iter().find(|&&index| index >= vertex_count) {
+                return Err(ModelError::IndexOutOfRange {
+                    face,
+                    index,
+                    vertex_count,
+                });
+            }
+            index_buffer.extend(triangulate(polygon));
+        }
+
+        let mut model = Model::from_buffers(vertex_buffer, index_buffer)?;
+        model.polygons = polygons;
+        Ok(model)
+    }
+
+    /// Constructs a model from its vertices and faces, checking that every face refers to
+    /// existing vertices.
+    pub fn from_buffers(
+        vertex_buffer: Vec<Vertex>,
+        index_buffer: Vec<Face>,
+    ) -> Result<Model, ModelError> {
+        let vertex_count = vertex_buffer.len();
+        for (face, indexes) in index_buffer.iter().map(Face::indexes).enumerate() {
+            let (a, b, c) = indexes;
+            if let Some(&index) = [a, b, c].iter().find(|&&index| index >= vertex_count) {
+                return Err(ModelError::IndexOutOfRange {
+                    face,
+                    index,
+                    vertex_count,
+                });
             }
         }
 
@@ -53,6 +191,14 @@ impl Model {
             index_buffer,
             position: Translation3::new(0.0, 0.0, 0.0),
             rotation: UnitQuaternion::from_euler_angles(0.0, 0.0, 0.0),
+            cull_mode: None,
+            render_mode: None,
+            line_style: None,
+            point_style: None,
+            shading: Shading::default(),
+            material: None,
+            polygons: vec![],
+            lods: vec![],
         })
     }
 
@@ -66,40 +212,33 @@ impl Model {
             for y in 0..div + 1 {
                 let offset = width / 2.0;
                 vertex_buffer.push(Vertex {
-                    position: Point3::new(
+                    uv: Some(Point2::new(x as f32 / div as f32, y as f32 / div as f32)),
+                    ..Vertex::new(Point3::new(
                         (x as f32 * div_length) - offset,
                         (y as f32 * div_length) - offset,
                         0.0,
-                    ),
+                    ))
                 });
             }
         }
 
         for y in 0..div {
             for x in 0..div {
-                let curr_index = ((x + 1) + y * (div + 1)) as usize;
-                index_buffer.push(Face {
-                    indexes: (curr_index, curr_index + (div + 1) as usize, curr_index + 1),
-                });
-                index_buffer.push(Face {
-                    indexes: (
-                        curr_index + 1,
-                        curr_index + (div + 1) as usize,
-                        curr_index + 1 + (div + 1) as usize,
-                    ),
-                });
+                let curr_index = (x + y * (div + 1)) as usize;
+                let next_row = curr_index + (div + 1) as usize;
+                index_buffer.push(Face::new(curr_index, next_row, curr_index + 1));
+                index_buffer.push(Face::new(curr_index + 1, next_row, next_row + 1));
             }
         }
 
-        Model {
-            vertex_buffer,
-            index_buffer,
-            position: Translation3::new(0.0, 0.0, 0.0),
-            rotation: UnitQuaternion::from_euler_angles(0.0, 0.0, 0.0),
-        }
+        Model::from_buffers(vertex_buffer, index_buffer).expect("Plane indexes are in range")
     }
 
     /// Returns the vertex at index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no vertex at the index.
     pub fn vertex_at(&self, index: usize) -> Vertex {
         self.vertex_buffer[index].clone()
     }
@@ -122,3 +261,128 @@ impl Model {
             UnitQuaternion::from_euler_angles(x.to_radians(), y.to_radians(), z.to_radians());
     }
 }
+
+/// Splits a convex polygon into a fan of triangles around its first vertex.
+fn triangulate(polygon: &[usize]) -> impl Iterator<Item = Face> + '_ {
+    polygon
+        .windows(2)
+        .skip(1)
+        .map(|edge| Face::new(polygon[0], edge[0], edge[1]))
+}
+
+/// How much of a file has been read, see [`Model::load_from_file_with_progress`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoadProgress {
+    pub bytes_read: u64,
+    pub total_bytes: u64,
+}
+
+impl LoadProgress {
+    /// Share of the file that has been read, between 0 and 1.
+    pub fn fraction(&self) -> f32 {
+        if self.total_bytes == 0 {
+            1.0
+        } else {
+            (self.bytes_read as f64 / self.total_bytes as f64).min(1.0) as f32
+        }
+    }
+}
+
+/// Error returned when a [`Model`] can not be constructed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ModelError {
+    /// A face refers to a vertex past the end of the vertex buffer.
+    IndexOutOfRange {
+        face: usize,
+        index: usize,
+        vertex_count: usize,
+    },
+    /// A face in an OBJ file refers to vertex 0, while OBJ indexes start at 1.
+    ZeroObjIndex { line: usize },
+    /// A polygon has fewer than three vertices.
+    DegeneratePolygon { face: usize },
+    /// The data is not a valid STL file.
+    InvalidStl(String),
+    /// The data is not a valid PLY file.
+    InvalidPly(String),
+    /// The data is not a valid OBJ file.
+    InvalidObj(String),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::IndexOutOfRange {
+                face,
+                index,
+                vertex_count,
+            } => write!(
+                f,
+                "face {face} refers to vertex {index}, but there are only {vertex_count} vertices"
+            ),
+            ModelError::InvalidStl(reason) => write!(f, "invalid STL file: {reason}"),
+            ModelError::InvalidPly(reason) => write!(f, "invalid PLY file: {reason}"),
+            ModelError::InvalidObj(reason) => write!(f, "invalid OBJ file: {reason}"),
+            ModelError::DegeneratePolygon { face } => {
+                write!(f, "face {face} has fewer than three vertices")
+            }
+            ModelError::ZeroObjIndex { line } => {
+                write!(
+                    f,
+                    "face on line {line} refers to vertex 0, OBJ indexes start at 1"
+                )
+            }
+        }
+    }
+}
+
+impl Error for ModelError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Model, ModelError};
+    use crate::renderer::{Face, Vertex};
+    use nalgebra::Point3;
+
+    fn vertices(count: usize) -> Vec<Vertex> {
+        (0..count)
+            .map(|i| Vertex::new(Point3::new(i as f32, 0.0, 0.0)))
+            .collect()
+    }
+
+    #[test]
+    fn test_from_buffers_checks_indexes() {
+        let model = Model::from_buffers(vertices(3), vec![Face::new(0, 1, 2)]).unwrap();
+        assert_eq!((0, 1, 2), model.index_buffer[0].indexes());
+
+        let faces = vec![Face::new(0, 1, 2), Face::new(1, 3, 2)];
+        assert_eq!(
+            ModelError::IndexOutOfRange {
+                face: 1,
+                index: 3,
+                vertex_count: 3
+            },
+            Model::from_buffers(vertices(3), faces).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_from_polygons_triangulates() {
+        let model = Model::from_polygons(vertices(5), vec![vec![0, 1, 2, 3, 4]]).unwrap();
+        let faces: Vec<_> = model.index_buffer.iter().map(Face::indexes).collect();
+        assert_eq!(vec![(0, 1, 2), (0, 2, 3), (0, 3, 4)], faces);
+        assert_eq!(vec![vec![0, 1, 2, 3, 4]], model.polygons);
+
+        assert_eq!(
+            ModelError::DegeneratePolygon { face: 0 },
+            Model::from_polygons(vertices(5), vec![vec![0, 1]]).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_plane_indexes_are_in_range() {
+        let plane = Model::new_plane(3, 1.0);
+        assert_eq!(16, plane.vertex_buffer.len());
+        assert_eq!(18, plane.index_buffer.len());
+    }
+}
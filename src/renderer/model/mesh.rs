@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+
+use nalgebra::{Point3, Vector3};
+
+use super::{triangulate, Model};
+use crate::renderer::{Face, Vertex};
+
+/// An axis-aligned box, given by its lowest and highest corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    /// The point halfway between the corners.
+    pub fn center(&self) -> Point3<f32> {
+        nalgebra::center(&self.min, &self.max)
+    }
+
+    /// Length of the box along each axis.
+    pub fn size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+}
+
+/// A sphere that contains every vertex of a model.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl Model {
+    /// The smallest axis-aligned box around the vertices, in model space. `None` if the model
+    /// has no vertices.
+    pub fn bounding_box(&self) -> Option<Aabb> {
+        let mut positions = self.vertex_buffer.iter().map(|vertex| vertex.position);
+        let first = positions.next()?;
+        Some(positions.fold(
+            Aabb {
+                min: first,
+                max: first,
+            },
+            |aabb, position| Aabb {
+                min: aabb.min.inf(&position),
+                max: aabb.max.sup(&position),
+            },
+        ))
+    }
+
+    /// A sphere around the vertices, in model space, centered on the bounding box. It is not
+    /// necessarily the smallest one. `None` if the model has no vertices.
+    pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        let center = self.bounding_box()?.center();
+        let radius = self
+            .vertex_buffer
+            .iter()
+            .map(|vertex| nalgebra::distance(&center, &vertex.position))
+            .fold(0.0, f32::max);
+        Some(BoundingSphere { center, radius })
+    }
+
+    /// Moves the vertices so that the center of the bounding box is at the origin of the model.
+    pub fn recenter(&mut self) {
+        self.lods.clear();
+        if let Some(aabb) = self.bounding_box() {
+            let offset = aabb.center().coords;
+            for vertex in &mut self.vertex_buffer {
+                vertex.position -= offset;
+            }
+        }
+    }
+
+    /// Recenters the model and scales it to fit in a sphere with a radius of 1.
+    pub fn normalize(&mut self) {
+        self.recenter();
+        self.lods.clear();
+        let radius = self.bounding_sphere().map_or(0.0, |sphere| sphere.radius);
+        if radius > 0.0 {
+            for vertex in &mut self.vertex_buffer {
+                vertex.position /= radius;
+            }
+        }
+    }
+
+    /// Merges vertices that are at most `epsilon` apart, keeping the first one of each group.
+    /// Faces that collapse as a result are removed.
+    pub fn weld(&mut self, epsilon: f32) {
+        let cell_size = epsilon.max(f32::EPSILON);
+        let cell_of = |position: &Point3<f32>| {
+            let cell = (position.coords / cell_size).map(|c| c.floor() as i64);
+            (cell.x, cell.y, cell.z)
+        };
+
+        // Welded vertices, bucketed by a grid as large as the tolerance
+        let mut grid: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+        let mut welded: Vec<Vertex> = Vec::with_capacity(self.vertex_buffer.len());
+        let mut remap = Vec::with_capacity(self.vertex_buffer.len());
+
+        for vertex in self.vertex_buffer.drain(..) {
+            let (x, y, z) = cell_of(&vertex.position);
+            let existing = (-1..=1)
+                .flat_map(|dx| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dz| (dx, dy, dz))))
+                .filter_map(|(dx, dy, dz)| grid.get(&(x + dx, y + dy, z + dz)))
+                .flatten()
+                .copied()
+                .find(|&index: &usize| {
+                    nalgebra::distance(&welded[index].position, &vertex.position) <= epsilon
+                });
+            match existing {
+                Some(index) => remap.push(index),
+                None => {
+                    grid.entry((x, y, z)).or_default().push(welded.len());
+                    remap.push(welded.len());
+                    welded.push(vertex);
+                }
+            }
+        }
+
+        self.vertex_buffer = welded;
+        self.lods.clear();
+        self.index_buffer.retain_mut(|face| {
+            let (a, b, c) = face.indexes;
+            face.indexes = (remap[a], remap[b], remap[c]);
+            let (a, b, c) = face.indexes;
+            a != b && b != c && c != a
+        });
+        self.polygons.retain_mut(|polygon| {
+            for index in polygon.iter_mut() {
+                *index = remap[*index];
+            }
+            polygon.dedup();
+            while polygon.len() > 1 && polygon.first() == polygon.last() {
+                polygon.pop();
+            }
+            polygon.len() >= 3
+        });
+    }
+
+    /// Appends the vertices and faces of another model. They are moved into the model space of
+    /// this model, so the result looks the same as drawing both. The other model's materials and
+    /// render settings are not carried over.
+    pub fn merge(&mut self, other: &Model) {
+        let transform = self.model_matrix().inverse() * other.model_matrix();
+        let offset = self.vertex_buffer.len();
+        self.lods.clear();
+
+        if !self.polygons.is_empty() || !other.polygons.is_empty() {
+            self.polygons = self.polygons();
+            self.polygons.extend(
+                other
+                    .polygons()
+                    .into_iter()
+                    .map(|polygon| polygon.into_iter().map(|index| index + offset).collect()),
+            );
+        }
+
+        self.vertex_buffer
+            .extend(other.vertex_buffer.iter().map(|vertex| {
+                let mut vertex = vertex.clone();
+                vertex.position = transform.transform_point(&vertex.position);
+                vertex.normal = vertex
+                    .normal
+                    .map(|normal| transform.transform_vector(&normal));
+                vertex
+            }));
+        self.index_buffer
+            .extend(other.index_buffer.iter().map(|face| {
+                let (a, b, c) = face.indexes;
+                Face {
+                    indexes: (a + offset, b + offset, c + offset),
+                    ..face.clone()
+                }
+            }));
+    }
+
+    /// Reverses the winding of every face, turning the model inside out. Vertex normals are
+    /// flipped along with it.
+    pub fn flip_winding(&mut self) {
+        self.lods.clear();
+        let has_fans = self.has_polygon_fans();
+        for face in &mut self.index_buffer {
+            let (a, b, c) = face.indexes;
+            face.indexes = (a, c, b);
+        }
+        // The fan of a reversed polygon has its triangles in reverse order
+        let mut first_triangle = 0;
+        for polygon in &mut self.polygons {
+            polygon[1..].reverse();
+            let end = first_triangle + polygon.len() - 2;
+            if has_fans {
+                self.index_buffer[first_triangle..end].reverse();
+            }
+            first_triangle = end;
+        }
+        for vertex in &mut self.vertex_buffer {
+            vertex.normal = vertex.normal.map(|normal| -normal);
+        }
+    }
+
+    /// Faces of the model as indexes into the vertex buffer, with quads and other polygons kept
+    /// as they were loaded. Catmull-Clark subdivision and OBJ export use these.
+    ///
+    /// Gives the triangles instead if the model has no other polygons, or if the index buffer no
+    /// longer consists of their fans in order, such as after editing it directly.
+    pub fn polygons(&self) -> Vec<Vec<usize>> {
+        if self.has_polygon_fans() {
+            return self.polygons.clone();
+        }
+        self.index_buffer
+            .iter()
+            .map(|face| {
+                let (a, b, c) = face.indexes;
+                vec![a, b, c]
+            })
+            .collect()
+    }
+
+    /// Whether the model has polygons and the index buffer is made of their fans, in order.
+    fn has_polygon_fans(&self) -> bool {
+        let fans = self
+            .polygons
+            .iter()
+            .flat_map(|polygon| triangulate(polygon));
+        !self.polygons.is_empty()
+            && fans
+                .map(|face| face.indexes)
+                .eq(self.index_buffer.iter().map(|face| face.indexes))
+    }
+
+    /// Computes the normal of every vertex from the faces around it, weighted by their area.
+    /// Vertices that are not part of any face are left without a normal.
+    pub fn recompute_normals(&mut self) {
+        self.lods.clear();
+        recompute_normals(&mut self.vertex_buffer, &self.index_buffer);
+    }
+}
+
+/// Sets the normals of the vertices like [`Model::recompute_normals`].
+pub(super) fn recompute_normals(vertex_buffer: &mut [Vertex], index_buffer: &[Face]) {
+    let mut normals = vec![Vector3::zeros(); vertex_buffer.len()];
+    for face in index_buffer {
+        let (a, b, c) = face.indexes;
+        let [p0, p1, p2] = [a, b, c].map(|index| vertex_buffer[index].position);
+        // Points out of counter-clockwise faces, its length is twice the area of the face
+        let normal = (p1 - p0).cross(&(p2 - p0));
+        for index in [a, b, c] {
+            normals[index] += normal;
+        }
+    }
+    for (vertex, normal) in vertex_buffer.iter_mut().zip(normals) {
+        vertex.normal = normal.try_normalize(f32::EPSILON);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::renderer::{model::Model, Face, Vertex};
+    use nalgebra::{Point3, Vector3};
+
+    fn triangle(offset: f32) -> [Vertex; 3] {
+        [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]
+            .map(|(x, y)| Vertex::new(Point3::new(x + offset, y, 0.0)))
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut plane = Model::new_plane(2, 2.0);
+        plane
+            .vertex_buffer
+            .iter_mut()
+            .for_each(|v| v.position.x += 3.0);
+        let aabb = plane.bounding_box().unwrap();
+        assert_eq!(Point3::new(2.0, -1.0, 0.0), aabb.min);
+        assert_eq!(Point3::new(4.0, 1.0, 0.0), aabb.max);
+
+        plane.normalize();
+        let sphere = plane.bounding_sphere().unwrap();
+        assert_eq!(Point3::origin(), sphere.center);
+        assert!((sphere.radius - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_weld_removes_duplicates_and_collapsed_faces() {
+        let vertices = [triangle(0.0), triangle(1e-4), triangle(5.0)].concat();
+        let faces = vec![Face::new(0, 1, 2), Face::new(3, 4, 5), Face::new(6, 7, 0)];
+        let mut model = Model::from_buffers(vertices, faces).unwrap();
+        model.weld(1e-3);
+        assert_eq!(6, model.vertex_buffer.len());
+        assert_eq!(3, model.index_buffer.len());
+        assert_eq!((0, 1, 2), model.index_buffer[1].indexes());
+
+        model.vertex_buffer[4].position = model.vertex_buffer[3].position;
+        model.weld(0.0);
+        assert_eq!(2, model.index_buffer.len());
+    }
+
+    #[test]
+    fn test_normals_follow_winding() {
+        let mut plane = Model::new_plane(2, 1.0);
+        plane.recompute_normals();
+        let expected = plane.vertex_buffer[0].normal.unwrap();
+        assert!((expected.z.abs() - 1.0).abs() < 1e-6);
+        assert!(plane
+            .vertex_buffer
+            .iter()
+            .all(|v| v.normal == Some(expected)));
+
+        plane.flip_winding();
+        plane.recompute_normals();
+        assert_eq!(Some(-expected), plane.vertex_buffer[4].normal);
+
+        // Normals point out of the side the vertices go around counter-clockwise
+        let mut triangle_model =
+            Model::from_buffers(triangle(0.0).to_vec(), vec![Face::new(0, 1, 2)]).unwrap();
+        triangle_model.recompute_normals();
+        assert_eq!(Some(Vector3::z()), triangle_model.vertex_buffer[0].normal);
+    }
+
+    #[test]
+    fn test_merge_applies_transforms() {
+        let triangle_model =
+            || Model::from_buffers(triangle(0.0).to_vec(), vec![Face::new(0, 1, 2)]).unwrap();
+        let mut model = triangle_model();
+        let mut other = triangle_model();
+        other.translate(2.0, 0.0, 0.0);
+        model.translate(1.0, 0.0, 0.0);
+        model.merge(&other);
+        assert_eq!(6, model.vertex_buffer.len());
+        assert_eq!((3, 4, 5), model.index_buffer[1].indexes());
+        assert_eq!(Point3::new(1.0, 0.0, 0.0), model.vertex_buffer[3].position);
+    }
+
+    #[test]
+    fn test_mesh_changes_clear_lods() {
+        let other = Model::new_plane(1, 1.0);
+        let changes: [&dyn Fn(&mut Model); 9] = [
+            &|model| model.recenter(),
+            &|model| model.normalize(),
+            &|model| model.weld(0.01),
+            &|model| model.merge(&other),
+            &|model| model.flip_winding(),
+            &|model| model.recompute_normals(),
+            &|model| model.subdivide_loop(),
+            &|model| model.subdivide_midpoint(),
+            &|model| model.subdivide_catmull_clark(),
+        ];
+        for change in changes {
+            let mut plane = Model::new_plane(4, 2.0);
+            plane.generate_lods(2);
+            change(&mut plane);
+            assert!(plane.lods.is_empty());
+        }
+    }
+}
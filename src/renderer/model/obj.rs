@@ -0,0 +1,593 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::File,
+    io::{self, BufRead, BufWriter, Write},
+    path::Path,
+};
+
+use nalgebra::{Point2, Point3, Vector3};
+
+use super::{ply::is_ply, stl::is_stl, triangulate, Model, ModelError, Resolver};
+use crate::renderer::{material::Material, Color, Face, Vertex};
+
+/// Name of the single material written to MTL files.
+const MATERIAL_NAME: &str = "bren";
+
+/// How often the progress callback is called while parsing, in bytes.
+const PROGRESS_INTERVAL: u64 = 1 << 20;
+
+/// Number of elements in an OBJ file, used to size the buffers before parsing.
+#[derive(Clone, Copy, Debug, Default)]
+pub(super) struct ObjCounts {
+    vertices: usize,
+    normals: usize,
+    uvs: usize,
+    faces: usize,
+}
+
+/// Whether the start of a file looks like an OBJ file, which is text that is neither a PLY nor
+/// an ASCII STL file.
+pub(super) fn is_obj(start: &[u8]) -> bool {
+    let is_text = match std::str::from_utf8(start) {
+        Ok(_) => true,
+        // A character may be cut off at the end
+        Err(error) => error.error_len().is_none(),
+    };
+    is_text && !start.contains(&0) && !is_ply(start) && !is_stl(start)
+}
+
+/// Counts the elements of an OBJ file by the start of its lines, without parsing them.
+pub(super) fn count_elements(mut reader: impl BufRead) -> io::Result<ObjCounts> {
+    let mut counts = ObjCounts::default();
+    let mut line = vec![];
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        match line.get(..2) {
+            Some(b"v ") => counts.vertices += 1,
+            Some(b"vn") => counts.normals += 1,
+            Some(b"vt") => counts.uvs += 1,
+            Some(b"f ") => counts.faces += 1,
+            _ => {}
+        }
+        line.clear();
+    }
+    Ok(counts)
+}
+
+/// Reads the next `N` numbers, returning how many there were.
+fn floats<'a, const N: usize>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+) -> Result<([f32; N], usize), Box<dyn Error>> {
+    let mut values = [0.0; N];
+    let mut count = 0;
+    for (value, token) in values.iter_mut().zip(tokens) {
+        *value = token.parse()?;
+        count += 1;
+    }
+    Ok((values, count))
+}
+
+/// State of an OBJ file while its lines are being parsed.
+struct ObjParser<'a> {
+    resolver: &'a dyn Resolver,
+    vertex_buffer: Vec<Vertex>,
+    normals: Vec<Vector3<f32>>,
+    uvs: Vec<Point2<f32>>,
+    index_buffer: Vec<Face>,
+    /// Number of vertices of every polygon, to rebuild them from their triangles.
+    polygon_sizes: Vec<usize>,
+    /// Corners of the face being parsed, reused between faces.
+    corners: Vec<usize>,
+    materials: HashMap<String, Material>,
+    current_material: Option<Material>,
+    /// Material of the first face, and whether all faces so far have used it.
+    first_material: Option<Option<Material>>,
+    uniform_material: bool,
+}
+
+impl<'a> ObjParser<'a> {
+    fn new(resolver: &'a dyn Resolver, counts: ObjCounts) -> ObjParser<'a> {
+        ObjParser {
+            resolver,
+            vertex_buffer: Vec::with_capacity(counts.vertices),
+            normals: Vec::with_capacity(counts.normals),
+            uvs: Vec::with_capacity(counts.uvs),
+            index_buffer: Vec::with_capacity(counts.faces),
+            polygon_sizes: Vec::with_capacity(counts.faces),
+            corners: vec![],
+            materials: HashMap::new(),
+            current_material: None,
+            first_material: None,
+            uniform_material: true,
+        }
+    }
+
+    fn parse_line(&mut self, line: &str, number: usize) -> Result<(), Box<dyn Error>> {
+        let mut tokens = line.split_ascii_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                // vertex data, with an optional colour
+                let ([x, y, z, r, g, b], count) = floats(&mut tokens)?;
+                if count < 3 {
+                    return Err(invalid(number, "vertex with fewer than three coordinates").into());
+                }
+                self.vertex_buffer.push(Vertex {
+                    color: (count == 6).then(|| Color::from(Vector3::new(r, g, b))),
+                    ..Vertex::new(Point3::new(x, y, z))
+                });
+            }
+            Some("vn") => {
+                if let ([x, y, z], 3) = floats(&mut tokens)? {
+                    self.normals.push(Vector3::new(x, y, z));
+                }
+            }
+            Some("vt") => {
+                // v is optional and defaults to 0
+                let ([u, v], count) = floats(&mut tokens)?;
+                if count == 0 {
+                    return Err(invalid(number, "texture coordinate without values").into());
+                }
+                self.uvs.push(Point2::new(u, v));
+            }
+            Some("mtllib") => {
+                for library in tokens {
+                    if let Ok(data) = self.resolver.resolve(library) {
+                        self.materials
+                            .extend(parse_mtl(&String::from_utf8_lossy(&data))?);
+                    }
+                }
+            }
+            Some("usemtl") => {
+                self.current_material = tokens
+                    .next()
+                    .and_then(|name| self.materials.get(name))
+                    .copied();
+            }
+            Some("f") => self.parse_face(tokens, number)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn parse_face<'l>(
+        &mut self,
+        tokens: impl Iterator<Item = &'l str>,
+        number: usize,
+    ) -> Result<(), Box<dyn Error>> {
+        // index data, starting at 1, as position/texture coordinates/normal
+        let index = |value: &str| -> Result<Option<usize>, Box<dyn Error>> {
+            if value.is_empty() {
+                return Ok(None);
+            }
+            let index: usize = value.parse()?;
+            Ok(Some(
+                index
+                    .checked_sub(1)
+                    .ok_or(ModelError::ZeroObjIndex { line: number + 1 })?,
+            ))
+        };
+
+        self.corners.clear();
+        for value in tokens {
+            let mut indexes = value.split('/');
+            let position = index(indexes.next().unwrap_or(value))?
+                .ok_or_else(|| invalid(number, "face corner without a vertex"))?;
+            let uv = indexes.next().map(index).transpose()?.flatten();
+            let normal = indexes.next().map(index).transpose()?.flatten();
+
+            // Texture coordinates and normals belong to the vertex, the first face using it
+            // decides them
+            if let Some(vertex) = self.vertex_buffer.get_mut(position) {
+                if let Some(uv) = uv {
+                    let uv = self.uvs.get(uv).ok_or_else(|| {
+                        invalid(number, "face refers to missing texture coordinates")
+                    })?;
+                    vertex.uv.get_or_insert(*uv);
+                }
+                if let Some(normal) = normal {
+                    let normal = self
+                        .normals
+                        .get(normal)
+                        .ok_or_else(|| invalid(number, "face refers to a missing normal"))?;
+                    vertex.normal.get_or_insert(*normal);
+                }
+            }
+            self.corners.push(position);
+        }
+        if self.corners.len() < 3 {
+            return Err(ModelError::DegeneratePolygon {
+                face: self.polygon_sizes.len(),
+            }
+            .into());
+        }
+
+        let color = self.current_material.map(|material| material.color);
+        self.index_buffer
+            .extend(triangulate(&self.corners).map(|face| Face { color, ..face }));
+        self.polygon_sizes.push(self.corners.len());
+        let first = *self.first_material.get_or_insert(self.current_material);
+        self.uniform_material &= first == self.current_material;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Model, Box<dyn Error>> {
+        let mut model = Model::from_buffers(self.vertex_buffer, self.index_buffer)?;
+        if let (true, Some(Some(material))) = (self.uniform_material, self.first_material) {
+            model.material = Some(material);
+            for face in &mut model.index_buffer {
+                face.color = None;
+            }
+        }
+
+        // Faces are triangulated into fans, in the order of their polygons
+        if self.polygon_sizes.iter().any(|&size| size != 3) {
+            let mut faces = model.index_buffer.iter().map(Face::indexes);
+            model.polygons = self
+                .polygon_sizes
+                .iter()
+                .map(|&size| {
+                    let mut fan = faces.by_ref().take(size - 2);
+                    let (a, b, c) = fan.next().expect("Polygons have at least one face");
+                    [a, b, c]
+                        .into_iter()
+                        .chain(fan.map(|(_, _, c)| c))
+                        .collect()
+                })
+                .collect();
+        }
+        Ok(model)
+    }
+}
+
+/// Parses an OBJ file line by line, calling `progress` with the number of bytes read so far.
+pub(super) fn parse_obj(
+    mut reader: impl BufRead,
+    resolver: &dyn Resolver,
+    counts: ObjCounts,
+    progress: &mut dyn FnMut(u64),
+) -> Result<Model, Box<dyn Error>> {
+    let mut parser = ObjParser::new(resolver, counts);
+    let mut line = String::new();
+    let mut bytes_read = 0;
+    let mut next_progress = PROGRESS_INTERVAL;
+    for number in 0.. {
+        line.clear();
+        let length = reader.read_line(&mut line)?;
+        if length == 0 {
+            break;
+        }
+        parser.parse_line(&line, number)?;
+
+        bytes_read += length as u64;
+        if bytes_read >= next_progress {
+            progress(bytes_read);
+            next_progress = bytes_read + PROGRESS_INTERVAL;
+        }
+    }
+    progress(bytes_read);
+    parser.finish()
+}
+
+impl Model {
+    /// Parses the contents of an OBJ file.
+    ///
+    /// Vertex colours are read from the common `v x y z r g b` extension, with the channels
+    /// between 0 and 1. Texture coordinates and normals are stored on the vertices, taken from the
+    /// first face corner that uses each vertex. Faces with more than three vertices are split into
+    /// triangles, and kept in [`Model::polygons`].
+    ///
+    /// Material libraries are read through the resolver, and libraries it can not find are
+    /// skipped. If all faces use the same material, its diffuse colour and dissolve become the
+    /// model's [`Material`], otherwise faces take the diffuse colour of their material.
+    pub fn from_obj(obj_file: &str, resolver: &dyn Resolver) -> Result<Model, Box<dyn Error>> {
+        let counts = count_elements(obj_file.as_bytes())?;
+        parse_obj(obj_file.as_bytes(), resolver, counts, &mut |_| {})
+    }
+
+    /// Saves the model to an OBJ file, in model space, so that it can be loaded again with
+    /// [`Model::load_from_file`] or inspected in other tools.
+    ///
+    /// With `with_material`, the colour and transparency of the model's material are saved to an
+    /// MTL file next to it, with the same name and the `.mtl` extension.
+    pub fn save_obj(&self, filename: &str, with_material: bool) -> Result<(), Box<dyn Error>> {
+        let path = Path::new(filename);
+        let material_library = if with_material {
+            let library = path.with_extension("mtl");
+            let mut writer = BufWriter::new(File::create(&library)?);
+            self.write_mtl(&mut writer)?;
+            writer.flush()?;
+            library
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(str::to_string)
+        } else {
+            None
+        };
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_obj(&mut writer, material_library.as_deref())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the model as an OBJ file, in model space. Polygons are written as they were loaded,
+    /// see [`Model::polygons`].
+    ///
+    /// Vertex colours are written with the `v x y z r g b` extension, vertices without one are
+    /// white. Normals and texture coordinates are written if every vertex has them. With a
+    /// material library, the faces use the material written by [`Model::write_mtl`].
+    pub fn write_obj(
+        &self,
+        writer: &mut impl Write,
+        material_library: Option<&str>,
+    ) -> std::io::Result<()> {
+        let has_colors = self
+            .vertex_buffer
+            .iter()
+            .any(|vertex| vertex.color.is_some());
+        let has_normals = !self.vertex_buffer.is_empty()
+            && self
+                .vertex_buffer
+                .iter()
+                .all(|vertex| vertex.normal.is_some());
+        let has_uvs = !self.vertex_buffer.is_empty()
+            && self.vertex_buffer.iter().all(|vertex| vertex.uv.is_some());
+
+        if let Some(library) = material_library {
+            writeln!(writer, "mtllib {library}")?;
+        }
+        for vertex in &self.vertex_buffer {
+            let p = vertex.position;
+            write!(writer, "v {} {} {}", p.x, p.y, p.z)?;
+            if has_colors {
+                let c = Vector3::from(vertex.color.unwrap_or(Color::WHITE));
+                write!(writer, " {} {} {}", c.x, c.y, c.z)?;
+            }
+            writeln!(writer)?;
+        }
+        for vertex in &self.vertex_buffer {
+            if let (true, Some(uv)) = (has_uvs, vertex.uv) {
+                writeln!(writer, "vt {} {}", uv.x, uv.y)?;
+            }
+        }
+        for vertex in &self.vertex_buffer {
+            if let (true, Some(n)) = (has_normals, vertex.normal) {
+                writeln!(writer, "vn {} {} {}", n.x, n.y, n.z)?;
+            }
+        }
+
+        if material_library.is_some() {
+            writeln!(writer, "usemtl {MATERIAL_NAME}")?;
+        }
+        for polygon in self.polygons() {
+            write!(writer, "f")?;
+            for index in polygon {
+                // Every vertex has its own texture coordinates and normal, at the same index
+                let index = index + 1;
+                match (has_uvs, has_normals) {
+                    (false, false) => write!(writer, " {index}")?,
+                    (true, false) => write!(writer, " {index}/{index}")?,
+                    (false, true) => write!(writer, " {index}//{index}")?,
+                    (true, true) => write!(writer, " {index}/{index}/{index}")?,
+                }
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the model's material as an MTL file, with the material's colour as the diffuse
+    /// colour and its alpha as the dissolve. Models without a material are white and opaque.
+    pub fn write_mtl(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let material = self.material.unwrap_or_default();
+        let color = Vector3::from(material.color);
+        writeln!(writer, "newmtl {MATERIAL_NAME}")?;
+        writeln!(writer, "Kd {} {} {}", color.x, color.y, color.z)?;
+        writeln!(writer, "d {}", material.alpha)?;
+        Ok(())
+    }
+}
+
+/// Parses the diffuse colour and dissolve of the materials in an MTL file.
+fn parse_mtl(mtl_file: &str) -> Result<HashMap<String, Material>, Box<dyn Error>> {
+    let mut materials = HashMap::new();
+    let mut current = None;
+    for line in mtl_file.lines() {
+        let values: Vec<&str> = line.split_whitespace().collect();
+        match values.as_slice() {
+            ["newmtl", name, ..] => {
+                current = Some(name.to_string());
+                materials.insert(name.to_string(), Material::default());
+            }
+            ["Kd", r, g, b, ..] => {
+                if let Some(material) = current.as_ref().and_then(|name| materials.get_mut(name)) {
+                    let color: Vector3<f32> = Vector3::new(r.parse()?, g.parse()?, b.parse()?);
+                    material.color = Color::from(color);
+                }
+            }
+            ["d", alpha, ..] | ["Tr", alpha, ..] => {
+                if let Some(material) = current.as_ref().and_then(|name| materials.get_mut(name)) {
+                    let alpha: f32 = alpha.parse()?;
+                    // Transparency is the opposite of dissolve
+                    material.alpha = if values[0] == "Tr" {
+                        1.0 - alpha
+                    } else {
+                        alpha
+                    };
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(materials)
+}
+
+fn invalid(line: usize, reason: &str) -> ModelError {
+    ModelError::InvalidObj(format!("{reason} on line {}", line + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::renderer::{
+        material::Material,
+        model::{Model, NoResolver, StlFormat},
+        Color, Face,
+    };
+    use nalgebra::Point2;
+    use std::{fs, io};
+
+    fn round_trip(model: &Model) -> Model {
+        let mut data = vec![];
+        model.write_obj(&mut data, None).unwrap();
+        Model::from_reader(data.as_slice(), &NoResolver).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_keeps_normals_and_uvs() {
+        let mut plane = Model::new_plane(2, 2.0);
+        plane.recompute_normals();
+        plane.vertex_buffer[4].color = Some(Color::RED);
+
+        let loaded = round_trip(&plane);
+        assert_eq!(plane.index_buffer.len(), loaded.index_buffer.len());
+        assert_eq!(plane.bounding_box(), loaded.bounding_box());
+        assert_eq!(
+            plane.vertex_buffer[0].normal,
+            loaded.vertex_buffer[0].normal
+        );
+        assert_eq!(Some(Point2::new(1.0, 0.5)), loaded.vertex_buffer[7].uv);
+        assert_eq!(Some(Color::RED), loaded.vertex_buffer[4].color);
+        assert_eq!(Some(Color::WHITE), loaded.vertex_buffer[0].color);
+    }
+
+    #[test]
+    fn test_polygons_and_material() {
+        let quad = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n";
+        let mut quad = round_trip(&Model::from_obj(quad, &NoResolver).unwrap());
+        assert_eq!(vec![vec![0, 1, 2, 3]], quad.polygons);
+
+        quad.material = Some(Material::transparent(Color::RED, 0.5));
+        let mut obj = vec![];
+        quad.write_obj(&mut obj, Some("quad.mtl")).unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        assert!(obj.starts_with("mtllib quad.mtl\n"));
+        assert!(obj.ends_with("usemtl bren\nf 1 2 3 4\n"));
+
+        let mut mtl = vec![];
+        quad.write_mtl(&mut mtl).unwrap();
+        assert_eq!(
+            "newmtl bren\nKd 1 0 0\nd 0.5\n",
+            String::from_utf8(mtl.clone()).unwrap()
+        );
+
+        let resolver = |path: &str| match path {
+            "quad.mtl" => Ok(mtl.clone()),
+            _ => Err(io::Error::from(io::ErrorKind::NotFound)),
+        };
+        let loaded = Model::from_obj(&obj, &resolver).unwrap();
+        assert_eq!(quad.material, loaded.material);
+    }
+
+    #[test]
+    fn test_written_polygons_follow_mesh_changes() {
+        let quads = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 2 0 0\nv 2 1 0\n\
+                     f 1 2 3 4\nf 2 5 6 3\n";
+        let written = |model: &Model| {
+            let mut obj = vec![];
+            model.write_obj(&mut obj, None).unwrap();
+            let obj = String::from_utf8(obj).unwrap();
+            obj.lines()
+                .filter(|line| line.starts_with("f "))
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        // Folding the first quad along its diagonal leaves only the triangles of the second one
+        let mut model = Model::from_obj(quads, &NoResolver).unwrap();
+        model.vertex_buffer[2].position = model.vertex_buffer[0].position;
+        model.weld(0.0);
+        assert_eq!(2, model.index_buffer.len());
+        assert_eq!(vec!["f 2 4 5", "f 2 5 1"], written(&model));
+
+        // Flipped quads stay quads
+        let mut model = Model::from_obj(quads, &NoResolver).unwrap();
+        model.flip_winding();
+        assert_eq!(vec![vec![0, 3, 2, 1], vec![1, 2, 5, 4]], model.polygons());
+        assert_eq!(vec!["f 1 4 3 2", "f 2 3 6 5"], written(&model));
+
+        // Faces added to the buffer directly are written as triangles
+        let mut model = Model::from_obj(quads, &NoResolver).unwrap();
+        model.index_buffer.push(Face::new(0, 1, 4));
+        assert_eq!(5, written(&model).len());
+        assert_eq!("f 1 2 5", written(&model)[4]);
+    }
+
+    #[test]
+    fn test_several_materials_color_faces() {
+        let mtl = "newmtl red\nKd 1 0 0\nnewmtl blue\nKd 0 0 1\n";
+        let obj = "mtllib colors.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                   usemtl red\nf 1 2 3 4\nusemtl blue\nf 1 3 2\n";
+        let resolver = |_: &str| Ok(mtl.as_bytes().to_vec());
+        let model = Model::from_obj(obj, &resolver).unwrap();
+        let colors: Vec<_> = model.index_buffer.iter().map(|face| face.color).collect();
+        let (red, blue) = (Some(Color::RED), Some(Color::BLUE));
+        assert_eq!(vec![red, red, blue], colors);
+        assert_eq!(None, model.material);
+
+        // Missing libraries only lose the colours
+        let model = Model::from_obj(obj, &NoResolver).unwrap();
+        assert!(model.index_buffer.iter().all(|face| face.color.is_none()));
+    }
+
+    #[test]
+    fn test_load_with_progress() {
+        let plane = Model::new_plane(40, 2.0);
+        let quads = plane
+            .index_buffer
+            .chunks(2)
+            .map(|pair| {
+                let ((a, b, c), (_, _, d)) = (pair[0].indexes(), pair[1].indexes());
+                vec![a, b, d, c]
+            })
+            .collect();
+        let plane = Model::from_polygons(plane.vertex_buffer, quads).unwrap();
+        let path = std::env::temp_dir().join(format!("bren-obj-{}.obj", std::process::id()));
+        plane.save_obj(path.to_str().unwrap(), false).unwrap();
+
+        let mut reports = vec![];
+        let loaded =
+            Model::load_from_file_with_progress(path.to_str().unwrap(), |p| reports.push(p))
+                .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(plane.polygons, loaded.polygons);
+        assert_eq!(plane.index_buffer.len(), loaded.index_buffer.len());
+        let last = reports.last().unwrap();
+        assert_eq!(last.total_bytes, last.bytes_read);
+        assert_eq!(1.0, last.fraction());
+    }
+
+    #[test]
+    fn test_from_reader_detects_binary_formats() {
+        let mut stl = vec![];
+        let plane = Model::new_plane(2, 1.0);
+        plane.write_stl(&mut stl, StlFormat::Binary).unwrap();
+        let loaded = Model::from_reader(stl.as_slice(), &NoResolver).unwrap();
+        assert_eq!(plane.index_buffer.len(), loaded.index_buffer.len());
+    }
+
+    #[test]
+    fn test_missing_normal() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1//1 2//1 3//1\n";
+        assert!(Model::from_obj(obj, &NoResolver).is_err());
+    }
+
+    #[test]
+    fn test_texture_coordinates_with_one_value() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0.5\nvt 1 1\nf 1/1 2/2 3/2\n";
+        let model = Model::from_obj(obj, &NoResolver).unwrap();
+        assert_eq!(Some(Point2::new(0.5, 0.0)), model.vertex_buffer[0].uv);
+        assert_eq!(Some(Point2::new(1.0, 1.0)), model.vertex_buffer[1].uv);
+        assert!(Model::from_obj("vt\n", &NoResolver).is_err());
+    }
+}
@@ -0,0 +1,407 @@
+use nalgebra::{Point3, Vector3};
+
+use super::{Model, ModelError};
+use crate::renderer::{Color, Vertex};
+
+/// Encoding of the body of a PLY file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+/// Scalar type of a property.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Scalar, ModelError> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(invalid(format!("unknown property type {name:?}"))),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    fn is_float(self) -> bool {
+        matches!(self, Scalar::F32 | Scalar::F64)
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(String, Scalar),
+    List(String, Scalar, Scalar),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+fn invalid(reason: impl Into<String>) -> ModelError {
+    ModelError::InvalidPly(reason.into())
+}
+
+/// Whether the data starts like a PLY file.
+pub(super) fn is_ply(data: &[u8]) -> bool {
+    data.starts_with(b"ply\n") || data.starts_with(b"ply\r\n")
+}
+
+/// Parses the header, returning the format, the elements and where the body starts.
+fn parse_header(data: &[u8]) -> Result<(Format, Vec<Element>, usize), ModelError> {
+    if !is_ply(data) {
+        return Err(invalid("missing \"ply\" magic number"));
+    }
+    let end = data
+        .windows(b"end_header".len())
+        .position(|window| window == b"end_header")
+        .ok_or_else(|| invalid("missing end_header"))?;
+    let body = data[end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(data.len(), |newline| end + newline + 1);
+    let header =
+        std::str::from_utf8(&data[..end]).map_err(|_| invalid("header is not valid UTF-8"))?;
+
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for line in header.lines().skip(1) {
+        let values: Vec<&str> = line.split_whitespace().collect();
+        match values.as_slice() {
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::LittleEndian,
+                    "binary_big_endian" => Format::BigEndian,
+                    _ => return Err(invalid(format!("unknown format {name:?}"))),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid element count {count:?}")))?,
+                properties: vec![],
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid("property before any element"))?
+                .properties
+                .push(Property::List(
+                    name.to_string(),
+                    Scalar::parse(count)?,
+                    Scalar::parse(item)?,
+                )),
+            ["property", scalar, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid("property before any element"))?
+                .properties
+                .push(Property::Scalar(name.to_string(), Scalar::parse(scalar)?)),
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(invalid(format!("unexpected header line {line:?}"))),
+        }
+    }
+
+    let format = format.ok_or_else(|| invalid("missing format"))?;
+    // Elements without properties take no room in the body, so their count is not bounded by it
+    if let Some(element) = elements
+        .iter()
+        .find(|element| element.properties.is_empty() && element.count > 0)
+    {
+        return Err(invalid(format!(
+            "element {:?} has no properties",
+            element.name
+        )));
+    }
+    Ok((format, elements, body))
+}
+
+/// Reads the values of the body one at a time, in either encoding.
+struct Body<'a> {
+    format: Format,
+    data: &'a [u8],
+    tokens: std::str::SplitAsciiWhitespace<'a>,
+}
+
+impl<'a> Body<'a> {
+    fn new(format: Format, data: &'a [u8]) -> Result<Body<'a>, ModelError> {
+        let text = match format {
+            Format::Ascii => {
+                std::str::from_utf8(data).map_err(|_| invalid("body is not valid UTF-8"))?
+            }
+            _ => "",
+        };
+        Ok(Body {
+            format,
+            data,
+            tokens: text.split_ascii_whitespace(),
+        })
+    }
+
+    /// Limits how many values are expected to what is left of the body, as each takes at least
+    /// a byte.
+    fn capacity(&self, count: usize) -> usize {
+        count.min(self.data.len())
+    }
+
+    fn read(&mut self, scalar: Scalar) -> Result<f64, ModelError> {
+        if self.format == Format::Ascii {
+            let token = self
+                .tokens
+                .next()
+                .ok_or_else(|| invalid("body ends early"))?;
+            return token
+                .parse()
+                .map_err(|_| invalid(format!("invalid value {token:?}")));
+        }
+
+        let size = scalar.size();
+        if self.data.len() < size {
+            return Err(invalid("body ends early"));
+        }
+        let (bytes, rest) = self.data.split_at(size);
+        self.data = rest;
+        let mut buffer = [0u8; 8];
+        buffer[..size].copy_from_slice(bytes);
+        if self.format == Format::BigEndian {
+            buffer[..size].reverse();
+        }
+        let [b0, b1, b2, b3, ..] = buffer;
+        Ok(match scalar {
+            Scalar::I8 => b0 as i8 as f64,
+            Scalar::U8 => b0 as f64,
+            Scalar::I16 => i16::from_le_bytes([b0, b1]) as f64,
+            Scalar::U16 => u16::from_le_bytes([b0, b1]) as f64,
+            Scalar::I32 => i32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::U32 => u32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::F32 => f32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::F64 => f64::from_le_bytes(buffer),
+        })
+    }
+}
+
+impl Model {
+    /// Parses a PLY file, in ASCII or in either binary encoding.
+    ///
+    /// Vertex positions are read from the `x`, `y` and `z` properties, normals from `nx`, `ny`
+    /// and `nz`, colours from `red`, `green` and `blue` and opacity from `alpha`, which are
+    /// between 0 and 255 for integers and between 0 and 1 for floats. Faces are read from the
+    /// `vertex_indices` list. Files without faces give a model of vertices only. Other elements
+    /// and properties are skipped.
+    pub fn from_ply(data: &[u8]) -> Result<Model, ModelError> {
+        let (format, elements, body) = parse_header(data)?;
+        let mut body = Body::new(format, &data[body..])?;
+
+        // The header gives the sizes of the buffers, but is not trusted with more than the body
+        // can hold
+        let count = |name: &str| {
+            elements
+                .iter()
+                .filter(|element| element.name == name)
+                .try_fold(0usize, |sum, element| sum.checked_add(element.count))
+                .ok_or_else(|| invalid(format!("too many {name} elements")))
+        };
+        let mut vertex_buffer = Vec::with_capacity(body.capacity(count("vertex")?));
+        let mut polygons = Vec::with_capacity(body.capacity(count("face")?));
+        for element in &elements {
+            for _ in 0..element.count {
+                let mut position = Vector3::zeros();
+                let mut normal: Vector3<f32> = Vector3::zeros();
+                let mut has_normal = false;
+                let mut color = [0u8; 3];
+                let mut has_color = false;
+                let mut alpha = None;
+
+                for property in &element.properties {
+                    match property {
+                        Property::Scalar(name, scalar) => {
+                            let value = body.read(*scalar)?;
+                            let channel = if scalar.is_float() {
+                                value * 255.0
+                            } else {
+                                value
+                            };
+                            let channel = channel.round().clamp(0.0, 255.0) as u8;
+                            match name.as_str() {
+                                "x" => position.x = value as f32,
+                                "y" => position.y = value as f32,
+                                "z" => position.z = value as f32,
+                                "nx" => normal.x = value as f32,
+                                "ny" => normal.y = value as f32,
+                                "nz" => normal.z = value as f32,
+                                "red" => color[0] = channel,
+                                "green" => color[1] = channel,
+                                "blue" => color[2] = channel,
+                                "alpha" => alpha = Some(f32::from(channel) / 255.0),
+                                _ => {}
+                            }
+                            has_normal |= matches!(name.as_str(), "nx" | "ny" | "nz");
+                            has_color |= matches!(name.as_str(), "red" | "green" | "blue");
+                        }
+                        Property::List(name, count, item) => {
+                            let count = body.read(*count)? as usize;
+                            let mut indexes = Vec::with_capacity(body.capacity(count));
+                            for _ in 0..count {
+                                indexes.push(body.read(*item)?);
+                            }
+                            if element.name == "face"
+                                && (name == "vertex_indices" || name == "vertex_index")
+                            {
+                                if indexes.iter().any(|&index| index < 0.0) {
+                                    return Err(invalid("negative vertex index"));
+                                }
+                                polygons.push(indexes.into_iter().map(|i| i as usize).collect());
+                            }
+                        }
+                    }
+                }
+
+                if element.name == "vertex" {
+                    vertex_buffer.push(Vertex {
+                        color: has_color.then(|| Color(color[0], color[1], color[2])),
+                        normal: has_normal.then_some(normal),
+                        alpha,
+                        ..Vertex::new(Point3::from(position))
+                    });
+                }
+            }
+        }
+
+        let mut model = Model::from_polygons(vertex_buffer, polygons)?;
+        if model.polygons.iter().all(|polygon| polygon.len() == 3) {
+            model.polygons.clear();
+        }
+        Ok(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::renderer::{model::Model, Color};
+    use nalgebra::{Point3, Vector3};
+
+    const HEADER: &str = "element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+    #[test]
+    fn test_ascii() {
+        let data = format!(
+            "ply\nformat ascii 1.0\ncomment test\n{HEADER}\
+             0 0 0 255 0 0\n1 0 0 0 255 0\n1 1 0 0 0 255\n0 1 0 255 255 255\n4 0 1 2 3\n"
+        );
+        let model = Model::from_ply(data.as_bytes()).unwrap();
+        assert_eq!(4, model.vertex_buffer.len());
+        assert_eq!(2, model.index_buffer.len());
+        assert_eq!(vec![vec![0, 1, 2, 3]], model.polygons);
+        assert_eq!(Some(Color::GREEN), model.vertex_buffer[1].color);
+        assert_eq!(Point3::new(1.0, 1.0, 0.0), model.vertex_buffer[2].position);
+    }
+
+    #[test]
+    fn test_alpha() {
+        let data = "ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nproperty float y\n\
+                    property float z\nproperty uchar alpha\nend_header\n0 0 0 51\n1 0 0 255\n";
+        let model = Model::from_ply(data.as_bytes()).unwrap();
+        assert_eq!(Some(0.2), model.vertex_buffer[0].alpha);
+        assert_eq!(Some(1.0), model.vertex_buffer[1].alpha);
+        assert_eq!(None, model.vertex_buffer[0].color);
+    }
+
+    #[test]
+    fn test_binary_point_cloud() {
+        for big_endian in [false, true] {
+            let format = if big_endian { "big" } else { "little" };
+            let mut data = format!(
+                "ply\nformat binary_{format}_endian 1.0\nelement vertex 2\n\
+                 property double x\nproperty double y\nproperty double z\n\
+                 property float nx\nproperty float ny\nproperty float nz\nend_header\n"
+            )
+            .into_bytes();
+            for (position, normal) in [([1.0f64, 2.0, 3.0], [0.0f32, 0.0, 1.0]); 2] {
+                for value in position {
+                    let bytes = if big_endian {
+                        value.to_be_bytes()
+                    } else {
+                        value.to_le_bytes()
+                    };
+                    data.extend_from_slice(&bytes);
+                }
+                for value in normal {
+                    let bytes = if big_endian {
+                        value.to_be_bytes()
+                    } else {
+                        value.to_le_bytes()
+                    };
+                    data.extend_from_slice(&bytes);
+                }
+            }
+            let model = Model::from_ply(&data).unwrap();
+            assert_eq!(2, model.vertex_buffer.len());
+            assert!(model.index_buffer.is_empty());
+            assert_eq!(Point3::new(1.0, 2.0, 3.0), model.vertex_buffer[1].position);
+            assert_eq!(Some(Vector3::z()), model.vertex_buffer[1].normal);
+        }
+    }
+
+    #[test]
+    fn test_truncated_body() {
+        let data = format!("ply\nformat ascii 1.0\n{HEADER}0 0 0 255 0 0\n");
+        assert!(Model::from_ply(data.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_counts_larger_than_the_body() {
+        let max = usize::MAX;
+        let huge_elements = format!(
+            "ply\nformat binary_little_endian 1.0\nelement vertex {max}\nproperty float x\n\
+             element vertex {max}\nproperty float x\nend_header\n"
+        );
+        assert!(Model::from_ply(huge_elements.as_bytes()).is_err());
+
+        let huge_list = b"ply\nformat binary_little_endian 1.0\nelement face 1\n\
+                         property list uint int vertex_indices\nend_header\n\xff\xff\xff\xff";
+        assert!(Model::from_ply(huge_list).is_err());
+
+        let empty_elements = "ply\nformat ascii 1.0\nelement vertex 4000000000\nend_header\n";
+        assert!(Model::from_ply(empty_elements.as_bytes()).is_err());
+        let no_elements = "ply\nformat ascii 1.0\nelement vertex 0\nend_header\n";
+        assert!(Model::from_ply(no_elements.as_bytes()).is_ok());
+    }
+}
@@ -0,0 +1,91 @@
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+/// Finds the files a model or scene refers to, such as OBJ material libraries and glTF buffers
+/// and images, given their path as written in the file being loaded.
+///
+/// Closures taking a path and returning the contents are resolvers too, which makes it easy to
+/// load from archives or from data that is already in memory.
+pub trait Resolver {
+    /// Reads the file at the path, which is usually relative to the file being loaded.
+    fn resolve(&self, path: &str) -> io::Result<Vec<u8>>;
+}
+
+impl<F: Fn(&str) -> io::Result<Vec<u8>>> Resolver for F {
+    fn resolve(&self, path: &str) -> io::Result<Vec<u8>> {
+        self(path)
+    }
+}
+
+/// Reads files from a directory on the local disk. Paths that are absolute or lead out of the
+/// directory with `..` are rejected, so files cannot make it read anything else.
+#[derive(Clone, Debug, Default)]
+pub struct FileResolver {
+    pub directory: PathBuf,
+}
+
+impl FileResolver {
+    pub fn new(directory: impl Into<PathBuf>) -> FileResolver {
+        FileResolver {
+            directory: directory.into(),
+        }
+    }
+
+    /// Resolves paths relative to the directory of the file.
+    pub fn for_file(filename: impl AsRef<Path>) -> FileResolver {
+        let directory = filename.as_ref().parent().unwrap_or(Path::new(""));
+        FileResolver::new(directory)
+    }
+}
+
+impl Resolver for FileResolver {
+    fn resolve(&self, path: &str) -> io::Result<Vec<u8>> {
+        let relative = Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !relative {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{path:?} is outside of {:?}", self.directory),
+            ));
+        }
+        fs::read(self.directory.join(path))
+    }
+}
+
+/// Resolver for data that refers to no other files. Every path fails to resolve.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoResolver;
+
+impl Resolver for NoResolver {
+    fn resolve(&self, path: &str) -> io::Result<Vec<u8>> {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no resolver for {path:?}"),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileResolver, Resolver};
+    use std::{env, fs, io};
+
+    #[test]
+    fn test_paths_outside_the_directory_are_rejected() {
+        let directory = env::temp_dir().join(format!("bren-resolver-{}", std::process::id()));
+        fs::create_dir_all(directory.join("textures")).unwrap();
+        fs::write(directory.join("textures/wood.ppm"), b"P3").unwrap();
+        let resolver = FileResolver::new(directory.join("textures"));
+
+        assert_eq!(b"P3".to_vec(), resolver.resolve("./wood.ppm").unwrap());
+        let outside = directory.join("textures/wood.ppm");
+        for path in ["../textures/wood.ppm", outside.to_str().unwrap()] {
+            let error = resolver.resolve(path).unwrap_err();
+            assert_eq!(io::ErrorKind::PermissionDenied, error.kind());
+        }
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
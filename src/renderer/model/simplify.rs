@@ -0,0 +1,526 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use nalgebra::{Matrix3, Matrix4, Point3, Vector3, Vector4};
+
+use super::{mesh::recompute_normals, Model};
+use crate::renderer::{Face, Vertex};
+
+/// A reduced version of a model's geometry, drawn instead of the full model when it is small on
+/// the screen. See [`Model::generate_lods`].
+#[derive(Clone, Debug)]
+pub struct Lod {
+    pub vertex_buffer: Vec<Vertex>,
+    pub index_buffer: Vec<Face>,
+}
+
+/// How strongly the open edges of a mesh are kept in place, relative to its surface.
+const BOUNDARY_WEIGHT: f64 = 100.0;
+
+/// Extra cost of collapsing an edge, relative to its squared length. Breaks ties in flat areas in
+/// favour of short edges, which keeps the triangles there evenly sized.
+const EDGE_LENGTH_WEIGHT: f64 = 1e-3;
+
+/// Cosine of the largest angle a face may turn by when an edge next to it collapses. Larger
+/// turns tend to fold the surface over.
+const MIN_FACE_TURN_COSINE: f64 = 0.2;
+
+/// A possible collapse of the edge between two vertices into a single point.
+struct Collapse {
+    cost: f64,
+    vertices: (usize, usize),
+    /// Versions of both vertices when the collapse was computed. Outdated collapses are skipped.
+    versions: (u32, u32),
+    target: Point3<f64>,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost.total_cmp(&other.cost) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Cheapest collapse first.
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// State of a mesh while its edges are being collapsed.
+struct Decimation {
+    positions: Vec<Point3<f64>>,
+    quadrics: Vec<Matrix4<f64>>,
+    versions: Vec<u32>,
+    removed: Vec<bool>,
+    faces: Vec<[usize; 3]>,
+    faces_removed: Vec<bool>,
+    /// Faces around each vertex, including removed ones.
+    vertex_faces: Vec<Vec<usize>>,
+    face_count: usize,
+}
+
+impl Decimation {
+    fn new(vertex_buffer: &[Vertex], index_buffer: &[Face]) -> Decimation {
+        let positions: Vec<Point3<f64>> = vertex_buffer
+            .iter()
+            .map(|vertex| vertex.position.cast())
+            .collect();
+        let faces: Vec<[usize; 3]> = index_buffer
+            .iter()
+            .map(|face| {
+                let (a, b, c) = face.indexes;
+                [a, b, c]
+            })
+            .collect();
+
+        let mut quadrics = vec![Matrix4::zeros(); positions.len()];
+        let mut vertex_faces = vec![vec![]; positions.len()];
+        let mut edge_faces: HashMap<(usize, usize), usize> = HashMap::new();
+        for (index, face) in faces.iter().enumerate() {
+            let [p0, p1, p2] = face.map(|vertex| positions[vertex]);
+            let normal = (p1 - p0).cross(&(p2 - p0));
+            let Some(normal) = normal.try_normalize(f64::EPSILON) else {
+                continue;
+            };
+            let quadric = plane_quadric(&normal, &p0);
+            for (i, &vertex) in face.iter().enumerate() {
+                quadrics[vertex] += quadric;
+                vertex_faces[vertex].push(index);
+                let next = face[(i + 1) % 3];
+                *edge_faces
+                    .entry((vertex.min(next), vertex.max(next)))
+                    .or_default() += 1;
+            }
+        }
+
+        // Open edges get a plane through them, perpendicular to their face, so that the outline
+        // of the mesh does not shrink
+        for face in &faces {
+            let [p0, p1, p2] = face.map(|vertex| positions[vertex]);
+            let Some(normal) = (p1 - p0).cross(&(p2 - p0)).try_normalize(f64::EPSILON) else {
+                continue;
+            };
+            for i in 0..3 {
+                let (a, b) = (face[i], face[(i + 1) % 3]);
+                if edge_faces[&(a.min(b), a.max(b))] != 1 {
+                    continue;
+                }
+                let edge = positions[b] - positions[a];
+                if let Some(side) = edge.cross(&normal).try_normalize(f64::EPSILON) {
+                    let quadric = plane_quadric(&side, &positions[a]) * BOUNDARY_WEIGHT;
+                    quadrics[a] += quadric;
+                    quadrics[b] += quadric;
+                }
+            }
+        }
+
+        Decimation {
+            versions: vec![0; positions.len()],
+            removed: vec![false; positions.len()],
+            faces_removed: vec![false; faces.len()],
+            face_count: faces.len(),
+            positions,
+            quadrics,
+            faces,
+            vertex_faces,
+        }
+    }
+
+    /// The best point to collapse an edge into, and the error of doing so.
+    fn collapse(&self, a: usize, b: usize) -> Collapse {
+        let quadric = self.quadrics[a] + self.quadrics[b];
+        let error = |point: &Point3<f64>| {
+            let v = point.to_homogeneous();
+            (v.transpose() * quadric * v)[0]
+        };
+
+        let system: Matrix3<f64> = quadric.fixed_view::<3, 3>(0, 0).into();
+        let optimal = system
+            .try_inverse()
+            .filter(|_| system.determinant().abs() > 1e-12)
+            .map(|inverse| Point3::from(inverse * -quadric.fixed_view::<3, 1>(0, 3)));
+        let midpoint = nalgebra::center(&self.positions[a], &self.positions[b]);
+        let target = optimal
+            .into_iter()
+            .chain([self.positions[a], self.positions[b], midpoint])
+            .min_by(|p, q| error(p).total_cmp(&error(q)))
+            .expect("There are candidate points");
+
+        Collapse {
+            cost: error(&target)
+                + EDGE_LENGTH_WEIGHT
+                    * nalgebra::distance_squared(&self.positions[a], &self.positions[b]),
+            vertices: (a, b),
+            versions: (self.versions[a], self.versions[b]),
+            target,
+        }
+    }
+
+    /// Vertices that share a face with the vertex.
+    fn neighbours(&self, vertex: usize) -> Vec<usize> {
+        let mut neighbours: Vec<usize> = self.vertex_faces[vertex]
+            .iter()
+            .filter(|&&face| !self.faces_removed[face])
+            .flat_map(|&face| self.faces[face])
+            .filter(|&other| other != vertex)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    }
+
+    /// Whether collapsing the edge would change the topology of the mesh, by folding faces onto
+    /// each other or pinching the surface. Only the faces on the edge may disappear, so the
+    /// vertices at both ends can only share the neighbours opposite of the edge, and no face of
+    /// one may become a copy of a face of the other. Edges with more than two faces are kept.
+    fn breaks_link(&self, a: usize, b: usize) -> bool {
+        let faces_of = |vertex: usize| {
+            self.vertex_faces[vertex]
+                .iter()
+                .filter(|&&face| !self.faces_removed[face])
+                .map(|&face| self.faces[face])
+        };
+        let opposite: Vec<usize> = faces_of(a)
+            .filter(|face| face.contains(&b))
+            .filter_map(|face| face.into_iter().find(|&v| v != a && v != b))
+            .collect();
+        if opposite.len() > 2 {
+            return true;
+        }
+
+        let a_neighbours = self.neighbours(a);
+        let b_neighbours = self.neighbours(b);
+        let pinches = a_neighbours
+            .iter()
+            .filter(|&&v| v != b && b_neighbours.binary_search(&v).is_ok())
+            .any(|v| !opposite.contains(v));
+        if pinches {
+            return true;
+        }
+
+        let sorted = |mut face: [usize; 3]| {
+            face.sort_unstable();
+            face
+        };
+        let a_faces: Vec<[usize; 3]> = faces_of(a).map(sorted).collect();
+        faces_of(b)
+            .filter(|face| !face.contains(&a))
+            .map(|face| sorted(face.map(|v| if v == b { a } else { v })))
+            .any(|face| a_faces.contains(&face))
+    }
+
+    /// Whether moving the vertex to the target would turn any face that is kept around too far,
+    /// see [`MIN_FACE_TURN_COSINE`].
+    fn flips_faces(&self, vertex: usize, other: usize, target: &Point3<f64>) -> bool {
+        self.vertex_faces[vertex]
+            .iter()
+            .filter(|&&face| !self.faces_removed[face] && !self.faces[face].contains(&other))
+            .any(|&face| {
+                let corners = self.faces[face];
+                let [p0, p1, p2] = corners.map(|v| self.positions[v]);
+                let [q0, q1, q2] = corners.map(|v| {
+                    if v == vertex {
+                        *target
+                    } else {
+                        self.positions[v]
+                    }
+                });
+                let before = (p1 - p0).cross(&(p2 - p0));
+                let after = (q1 - q0).cross(&(q2 - q0));
+                before.dot(&after) <= MIN_FACE_TURN_COSINE * before.norm() * after.norm()
+            })
+    }
+
+    /// Collapses the second vertex of the edge into the first.
+    fn apply(&mut self, collapse: &Collapse) {
+        let (a, b) = collapse.vertices;
+        self.positions[a] = collapse.target;
+        self.quadrics[a] = self.quadrics[a] + self.quadrics[b];
+        self.removed[b] = true;
+        self.versions[a] += 1;
+
+        let b_faces = std::mem::take(&mut self.vertex_faces[b]);
+        for face in b_faces {
+            if self.faces_removed[face] {
+                continue;
+            }
+            if self.faces[face].contains(&a) {
+                self.faces_removed[face] = true;
+                self.face_count -= 1;
+            } else {
+                for vertex in &mut self.faces[face] {
+                    if *vertex == b {
+                        *vertex = a;
+                    }
+                }
+                self.vertex_faces[a].push(face);
+            }
+        }
+        let faces_removed = &self.faces_removed;
+        self.vertex_faces[a].retain(|&face| !faces_removed[face]);
+    }
+}
+
+/// Quadric of the squared distance to a plane, given by its unit normal and a point on it.
+fn plane_quadric(normal: &Vector3<f64>, point: &Point3<f64>) -> Matrix4<f64> {
+    let plane = Vector4::new(normal.x, normal.y, normal.z, -normal.dot(&point.coords));
+    plane * plane.transpose()
+}
+
+/// Reduces a mesh to at most `target_faces` faces, like [`Model::simplified`].
+fn simplify(vertex_buffer: &[Vertex], index_buffer: &[Face], target_faces: usize) -> Lod {
+    let mut decimation = Decimation::new(vertex_buffer, index_buffer);
+
+    let mut heap = BinaryHeap::new();
+    for face in &decimation.faces {
+        for i in 0..3 {
+            let (a, b) = (face[i], face[(i + 1) % 3]);
+            if a < b {
+                heap.push(decimation.collapse(a, b));
+            }
+        }
+    }
+
+    while decimation.face_count > target_faces {
+        let Some(collapse) = heap.pop() else {
+            break;
+        };
+        let (a, b) = collapse.vertices;
+        if decimation.removed[a]
+            || decimation.removed[b]
+            || collapse.versions != (decimation.versions[a], decimation.versions[b])
+            || decimation.breaks_link(a, b)
+            || decimation.flips_faces(a, b, &collapse.target)
+            || decimation.flips_faces(b, a, &collapse.target)
+        {
+            continue;
+        }
+        decimation.apply(&collapse);
+        // Only the edges around the moved vertex change their cost
+        for neighbour in decimation.neighbours(a) {
+            heap.push(decimation.collapse(a, neighbour));
+        }
+    }
+
+    // Drop removed faces and the vertices no face uses anymore
+    let mut remap = vec![None; decimation.positions.len()];
+    let mut vertices = vec![];
+    let mut faces = vec![];
+    for (index, face) in decimation.faces.iter().enumerate() {
+        if decimation.faces_removed[index] {
+            continue;
+        }
+        let [a, b, c] = face.map(|vertex| {
+            *remap[vertex].get_or_insert_with(|| {
+                let mut new = vertex_buffer[vertex].clone();
+                new.position = decimation.positions[vertex].cast();
+                vertices.push(new);
+                vertices.len() - 1
+            })
+        });
+        faces.push(Face {
+            indexes: (a, b, c),
+            ..index_buffer[index].clone()
+        });
+    }
+
+    if vertex_buffer.iter().any(|vertex| vertex.normal.is_some()) {
+        recompute_normals(&mut vertices, &faces);
+    }
+    Lod {
+        vertex_buffer: vertices,
+        index_buffer: faces,
+    }
+}
+
+impl Model {
+    /// Returns a copy of the model reduced to at most `target_faces` faces, or as close as it
+    /// gets without folding faces over. Edges are collapsed in order of the quadric error metric,
+    /// which keeps flat areas coarse and preserves sharp features and open edges.
+    ///
+    /// Collapsed vertices keep the colour of one of the original vertices. Vertex normals are
+    /// recomputed if the model had any.
+    pub fn simplified(&self, target_faces: usize) -> Model {
+        let Lod {
+            vertex_buffer,
+            index_buffer,
+        } = simplify(&self.vertex_buffer, &self.index_buffer, target_faces);
+        Model {
+            vertex_buffer,
+            index_buffer,
+            position: self.position,
+            rotation: self.rotation,
+            cull_mode: self.cull_mode,
+            render_mode: self.render_mode,
+            line_style: self.line_style,
+            point_style: self.point_style,
+            shading: self.shading,
+            material: self.material,
+            polygons: vec![],
+            lods: vec![],
+        }
+    }
+
+    /// Replaces the levels of detail of the model with `count` simplified versions, each with
+    /// about half the faces of the one before and simplified from it.
+    pub fn generate_lods(&mut self, count: usize) {
+        let mut lods: Vec<Lod> = Vec::with_capacity(count);
+        let mut target = self.index_buffer.len();
+        for _ in 0..count {
+            target /= 2;
+            let lod = match lods.last() {
+                Some(previous) => simplify(&previous.vertex_buffer, &previous.index_buffer, target),
+                None => simplify(&self.vertex_buffer, &self.index_buffer, target),
+            };
+            lods.push(lod);
+        }
+        self.lods = lods;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::renderer::{model::Model, CullMode, Face, Shading, Vertex};
+    use nalgebra::Point3;
+
+    #[test]
+    fn test_simplified_plane_keeps_its_outline() {
+        let plane = Model::new_plane(8, 2.0);
+        let simplified = plane.simplified(10);
+        assert!(simplified.index_buffer.len() <= 10);
+        assert!(simplified.vertex_buffer.len() < plane.vertex_buffer.len());
+        assert_eq!(plane.bounding_box(), simplified.bounding_box());
+        assert!(simplified
+            .vertex_buffer
+            .iter()
+            .all(|vertex| vertex.position.z.abs() < 1e-5));
+    }
+
+    #[test]
+    fn test_generate_lods() {
+        let mut plane = Model::new_plane(8, 2.0);
+        plane.generate_lods(3);
+        let faces: Vec<usize> = plane
+            .lods
+            .iter()
+            .map(|lod| lod.index_buffer.len())
+            .collect();
+        assert_eq!(3, faces.len());
+        assert!(faces[0] <= 64 && faces[1] <= 32 && faces[2] <= 16);
+    }
+
+    #[test]
+    fn test_simplified_keeps_settings_but_not_lods() {
+        let mut plane = Model::new_plane(8, 2.0);
+        plane.shading = Shading::Flat;
+        plane.cull_mode = Some(CullMode::None);
+        plane.generate_lods(2);
+        let simplified = plane.simplified(10);
+        assert_eq!(Shading::Flat, simplified.shading);
+        assert_eq!(Some(CullMode::None), simplified.cull_mode);
+        assert!(simplified.lods.is_empty());
+    }
+
+    /// Asserts that the model has no flat or repeated faces, and that every edge has one face on
+    /// each side.
+    fn assert_closed(model: &Model) {
+        let mut faces: Vec<[usize; 3]> = model
+            .index_buffer
+            .iter()
+            .map(|face| {
+                let (a, b, c) = face.indexes;
+                [a, b, c]
+            })
+            .collect();
+        for face in &faces {
+            let [p0, p1, p2] = face.map(|v| model.vertex_buffer[v].position);
+            assert!((p1 - p0).cross(&(p2 - p0)).norm() > 1e-6);
+        }
+
+        let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+        for face in &faces {
+            for i in 0..3 {
+                *edges.entry((face[i], face[(i + 1) % 3])).or_default() += 1;
+            }
+        }
+        assert!(edges
+            .iter()
+            .all(|(&(a, b), &count)| count == 1 && edges.get(&(b, a)) == Some(&1)));
+
+        for face in &mut faces {
+            face.sort_unstable();
+        }
+        faces.sort_unstable();
+        let count = faces.len();
+        faces.dedup();
+        assert_eq!(count, faces.len());
+    }
+
+    #[test]
+    fn test_simplified_sphere_stays_closed() {
+        // An octahedron, split into 512 faces and pushed out onto a sphere
+        let vertices = [
+            (1.0, 0.0, 0.0),
+            (-1.0, 0.0, 0.0),
+            (0.0, 1.0, 0.0),
+            (0.0, -1.0, 0.0),
+            (0.0, 0.0, 1.0),
+            (0.0, 0.0, -1.0),
+        ]
+        .map(|(x, y, z)| Vertex::new(Point3::new(x, y, z)));
+        let faces = [
+            (0, 2, 4),
+            (2, 1, 4),
+            (1, 3, 4),
+            (3, 0, 4),
+            (2, 0, 5),
+            (1, 2, 5),
+            (3, 1, 5),
+            (0, 3, 5),
+        ]
+        .map(|(a, b, c)| Face::new(a, b, c));
+        let mut sphere = Model::from_buffers(vertices.to_vec(), faces.to_vec()).unwrap();
+        for _ in 0..3 {
+            sphere.subdivide_midpoint();
+        }
+        for vertex in &mut sphere.vertex_buffer {
+            vertex.position = Point3::from(vertex.position.coords.normalize());
+        }
+
+        for target in [100, 20, 4] {
+            let simplified = sphere.simplified(target);
+            assert!(simplified.index_buffer.len() < sphere.index_buffer.len());
+            assert_closed(&simplified);
+        }
+    }
+
+    #[test]
+    fn test_tetrahedron_is_not_folded_flat() {
+        let vertices = [
+            (0.0, 0.0, 0.0),
+            (1.0, 0.0, 0.0),
+            (0.0, 1.0, 0.0),
+            (0.0, 0.0, 1.0),
+        ]
+        .map(|(x, y, z)| Vertex::new(Point3::new(x, y, z)));
+        let faces =
+            [(0, 2, 1), (0, 1, 3), (0, 3, 2), (1, 2, 3)].map(|(a, b, c)| Face::new(a, b, c));
+        let tetrahedron = Model::from_buffers(vertices.to_vec(), faces.to_vec()).unwrap();
+        let simplified = tetrahedron.simplified(0);
+        assert_eq!(4, simplified.index_buffer.len());
+        assert_closed(&simplified);
+    }
+}
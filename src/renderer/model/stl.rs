@@ -0,0 +1,255 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use nalgebra::{Point3, Vector3};
+
+use super::{Model, ModelError};
+use crate::renderer::{Face, Vertex};
+
+/// Encoding of an STL file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StlFormat {
+    /// Human readable text.
+    Ascii,
+    /// Compact binary encoding, the one most CAD software writes.
+    #[default]
+    Binary,
+}
+
+const BINARY_HEADER_SIZE: usize = 80;
+const BINARY_TRIANGLE_SIZE: usize = 50;
+
+/// Whether the data is a binary STL file, based on its size matching the triangle count in its
+/// header. Binary files may start with `solid` too, so the size is checked first.
+fn is_binary(data: &[u8]) -> bool {
+    let Some(count) = data.get(BINARY_HEADER_SIZE..BINARY_HEADER_SIZE + 4) else {
+        return false;
+    };
+    let count = u32::from_le_bytes(count.try_into().expect("Slice has four bytes")) as usize;
+    data.len() == BINARY_HEADER_SIZE + 4 + count * BINARY_TRIANGLE_SIZE
+}
+
+fn is_ascii(data: &[u8]) -> bool {
+    let start = data
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(0);
+    data[start..].starts_with(b"solid")
+}
+
+/// Whether the data looks like an STL file, in either encoding.
+pub(super) fn is_stl(data: &[u8]) -> bool {
+    is_binary(data) || is_ascii(data)
+}
+
+/// Collects the unindexed triangles of an STL file into a shared vertex buffer, joining corners
+/// at exactly the same position.
+#[derive(Default)]
+struct Welder {
+    vertex_buffer: Vec<Vertex>,
+    index_buffer: Vec<Face>,
+    indexes: HashMap<[u32; 3], usize>,
+}
+
+impl Welder {
+    fn index(&mut self, position: Point3<f32>) -> usize {
+        // -0.0 and 0.0 are the same position
+        let key = position.coords.map(|c| (c + 0.0).to_bits());
+        *self
+            .indexes
+            .entry([key.x, key.y, key.z])
+            .or_insert_with(|| {
+                self.vertex_buffer.push(Vertex::new(position));
+                self.vertex_buffer.len() - 1
+            })
+    }
+
+    fn push(&mut self, [a, b, c]: [Point3<f32>; 3]) {
+        let face = Face::new(self.index(a), self.index(b), self.index(c));
+        self.index_buffer.push(face);
+    }
+
+    fn finish(self) -> Result<Model, ModelError> {
+        Model::from_buffers(self.vertex_buffer, self.index_buffer)
+    }
+}
+
+impl Model {
+    /// Parses an STL file in either encoding. STL stores every triangle separately, so corners at
+    /// the same position are joined into a single vertex. The facet normals in the file are
+    /// ignored, the faces are oriented by the order of their corners.
+    pub fn from_stl(data: &[u8]) -> Result<Model, ModelError> {
+        if is_binary(data) {
+            Model::from_binary_stl(data)
+        } else if is_ascii(data) {
+            let text = std::str::from_utf8(data)
+                .map_err(|_| ModelError::InvalidStl("text is not valid UTF-8".to_string()))?;
+            Model::from_ascii_stl(text)
+        } else {
+            Err(ModelError::InvalidStl(
+                "neither a binary nor an ASCII STL file".to_string(),
+            ))
+        }
+    }
+
+    fn from_binary_stl(data: &[u8]) -> Result<Model, ModelError> {
+        // Closed meshes have about half as many vertices as triangles
+        let triangles = (data.len() - BINARY_HEADER_SIZE - 4) / BINARY_TRIANGLE_SIZE;
+        let mut welder = Welder {
+            vertex_buffer: Vec::with_capacity(triangles / 2),
+            index_buffer: Vec::with_capacity(triangles),
+            indexes: HashMap::with_capacity(triangles / 2),
+        };
+        for triangle in data[BINARY_HEADER_SIZE + 4..].chunks_exact(BINARY_TRIANGLE_SIZE) {
+            let float = |index: usize| {
+                let offset = 12 + index * 4;
+                f32::from_le_bytes(triangle[offset..offset + 4].try_into().expect("Four bytes"))
+            };
+            let corner = |i: usize| Point3::new(float(i * 3), float(i * 3 + 1), float(i * 3 + 2));
+            welder.push([corner(0), corner(1), corner(2)]);
+        }
+        welder.finish()
+    }
+
+    fn from_ascii_stl(text: &str) -> Result<Model, ModelError> {
+        let mut welder = Welder::default();
+        let mut corners = Vec::with_capacity(3);
+        for (number, line) in text.lines().enumerate() {
+            let values: Vec<&str> = line.split_whitespace().collect();
+            match values.first() {
+                Some(&"vertex") => {
+                    let invalid =
+                        || ModelError::InvalidStl(format!("invalid vertex on line {}", number + 1));
+                    if values.len() != 4 {
+                        return Err(invalid());
+                    }
+                    let coordinate = |i: usize| values[i].parse::<f32>().map_err(|_| invalid());
+                    corners.push(Point3::new(coordinate(1)?, coordinate(2)?, coordinate(3)?));
+                }
+                Some(&"endloop") => {
+                    let triangle: [Point3<f32>; 3] =
+                        corners.as_slice().try_into().map_err(|_| {
+                            ModelError::InvalidStl(format!(
+                                "facet ending on line {} does not have three vertices",
+                                number + 1
+                            ))
+                        })?;
+                    welder.push(triangle);
+                    corners.clear();
+                }
+                _ => {}
+            }
+        }
+        welder.finish()
+    }
+
+    /// Saves the model's triangles to an STL file, in model space.
+    pub fn save_stl(&self, filename: &str, format: StlFormat) -> Result<(), Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        self.write_stl(&mut writer, format)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the model's triangles as an STL file, in model space.
+    pub fn write_stl(&self, writer: &mut impl Write, format: StlFormat) -> std::io::Result<()> {
+        let triangles = self.index_buffer.iter().map(|face| {
+            let (a, b, c) = face.indexes;
+            let corners = [a, b, c].map(|index| self.vertex_buffer[index].position);
+            let normal = (corners[1] - corners[0])
+                .cross(&(corners[2] - corners[0]))
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::zeros);
+            (normal, corners)
+        });
+
+        match format {
+            StlFormat::Binary => {
+                let mut header = [0u8; BINARY_HEADER_SIZE];
+                let name = b"bren";
+                header[..name.len()].copy_from_slice(name);
+                writer.write_all(&header)?;
+                writer.write_all(&(self.index_buffer.len() as u32).to_le_bytes())?;
+                for (normal, corners) in triangles {
+                    for value in normal
+                        .iter()
+                        .chain(corners.iter().flat_map(|p| p.coords.iter()))
+                    {
+                        writer.write_all(&value.to_le_bytes())?;
+                    }
+                    writer.write_all(&0u16.to_le_bytes())?;
+                }
+            }
+            StlFormat::Ascii => {
+                writeln!(writer, "solid bren")?;
+                for (normal, corners) in triangles {
+                    writeln!(
+                        writer,
+                        "  facet normal {} {} {}",
+                        normal.x, normal.y, normal.z
+                    )?;
+                    writeln!(writer, "    outer loop")?;
+                    for corner in corners {
+                        writeln!(
+                            writer,
+                            "      vertex {} {} {}",
+                            corner.x, corner.y, corner.z
+                        )?;
+                    }
+                    writeln!(writer, "    endloop")?;
+                    writeln!(writer, "  endfacet")?;
+                }
+                writeln!(writer, "endsolid bren")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_binary, StlFormat};
+    use crate::renderer::model::{Model, ModelError};
+
+    fn round_trip(model: &Model, format: StlFormat) -> Model {
+        let mut data = vec![];
+        model.write_stl(&mut data, format).unwrap();
+        Model::from_stl(&data).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_welds_vertices() {
+        let plane = Model::new_plane(3, 2.0);
+        for format in [StlFormat::Ascii, StlFormat::Binary] {
+            let loaded = round_trip(&plane, format);
+            assert_eq!(plane.vertex_buffer.len(), loaded.vertex_buffer.len());
+            assert_eq!(plane.index_buffer.len(), loaded.index_buffer.len());
+            assert_eq!(plane.bounding_box(), loaded.bounding_box());
+        }
+    }
+
+    #[test]
+    fn test_binary_header_may_start_with_solid() {
+        let mut data = vec![];
+        Model::new_plane(1, 1.0)
+            .write_stl(&mut data, StlFormat::Binary)
+            .unwrap();
+        data[..6].copy_from_slice(b"solid ");
+        assert!(is_binary(&data));
+        assert_eq!(2, Model::from_stl(&data).unwrap().index_buffer.len());
+    }
+
+    #[test]
+    fn test_invalid_ascii() {
+        let text = "solid x\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0\nendloop\n";
+        assert!(matches!(
+            Model::from_stl(text.as_bytes()),
+            Err(ModelError::InvalidStl(_))
+        ));
+        assert!(Model::from_stl(b"v 0 0 0").is_err());
+    }
+}
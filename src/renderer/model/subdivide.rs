@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+
+use nalgebra::{Point2, Point3, Vector2, Vector3};
+
+use super::{triangulate, Model};
+use crate::renderer::{Color, Face, Vertex};
+
+/// Key of the edge between two vertices, independent of direction.
+fn edge(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+/// A new vertex from a weighted combination of existing ones, with weights that sum to 1.
+/// Colours are mixed in linear light, texture coordinates only if all vertices have them, and
+/// normals are left out.
+fn blend(vertices: &[Vertex], weights: &[(usize, f32)]) -> Vertex {
+    let mut position = Vector3::zeros();
+    let mut color = Vector3::zeros();
+    let mut colored = false;
+    let mut alpha = 0.0;
+    let mut translucent = false;
+    let mut uv = Some(Vector2::zeros());
+    for &(index, weight) in weights {
+        let vertex = &vertices[index];
+        position += vertex.position.coords * weight;
+        colored |= vertex.color.is_some();
+        color += vertex.color.unwrap_or(Color::WHITE).to_linear() * weight;
+        translucent |= vertex.alpha.is_some();
+        alpha += vertex.alpha.unwrap_or(1.0) * weight;
+        uv = uv.zip(vertex.uv).map(|(sum, uv)| sum + uv.coords * weight);
+    }
+    Vertex {
+        color: colored.then(|| Color::from_linear(color)),
+        uv: uv.map(Point2::from),
+        alpha: translucent.then_some(alpha),
+        ..Vertex::new(Point3::from(position))
+    }
+}
+
+/// Neighbouring vertices of every vertex, and the neighbours along open edges.
+fn neighbours(
+    vertex_count: usize,
+    edge_faces: &HashMap<(usize, usize), Vec<usize>>,
+) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+    let mut neighbours = vec![vec![]; vertex_count];
+    let mut boundary = vec![vec![]; vertex_count];
+    for (&(a, b), faces) in edge_faces {
+        neighbours[a].push(b);
+        neighbours[b].push(a);
+        if faces.len() == 1 {
+            boundary[a].push(b);
+            boundary[b].push(a);
+        }
+    }
+    (neighbours, boundary)
+}
+
+impl Model {
+    /// Splits every triangle into four with Loop subdivision, which moves the vertices to
+    /// approach a smooth surface. Open edges stay open, and are smoothed as curves.
+    ///
+    /// Polygons are replaced by their triangles. Vertex normals are recomputed if the model had
+    /// any.
+    pub fn subdivide_loop(&mut self) {
+        self.subdivide_triangles(true);
+    }
+
+    /// Splits every triangle into four at the midpoints of its edges, without changing the shape
+    /// of the model.
+    ///
+    /// Polygons are replaced by their triangles. Vertex normals are recomputed if the model had
+    /// any.
+    pub fn subdivide_midpoint(&mut self) {
+        self.subdivide_triangles(false);
+    }
+
+    fn subdivide_triangles(&mut self, smooth: bool) {
+        let had_normals = self.has_normals();
+        let vertices = &self.vertex_buffer;
+
+        // Vertices opposite of every edge, one for each face next to it
+        let mut opposite: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for face in &self.index_buffer {
+            let (a, b, c) = face.indexes;
+            for (x, y, z) in [(a, b, c), (b, c, a), (c, a, b)] {
+                opposite.entry(edge(x, y)).or_default().push(z);
+            }
+        }
+
+        let mut vertex_buffer: Vec<Vertex> = if smooth {
+            let (neighbours, boundary) = neighbours(vertices.len(), &opposite);
+            (0..vertices.len())
+                .map(|vertex| {
+                    let weights = loop_weights(vertex, &neighbours[vertex], &boundary[vertex]);
+                    blend(vertices, &weights)
+                })
+                .collect()
+        } else {
+            vertices.clone()
+        };
+
+        let mut edge_points: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut index_buffer = Vec::with_capacity(self.index_buffer.len() * 4);
+        for face in &self.index_buffer {
+            let (a, b, c) = face.indexes;
+            let [ab, bc, ca] = [(a, b), (b, c), (c, a)].map(|(x, y)| {
+                *edge_points.entry(edge(x, y)).or_insert_with(|| {
+                    let weights = match (smooth, opposite[&edge(x, y)].as_slice()) {
+                        (true, &[c, d]) => vec![(x, 0.375), (y, 0.375), (c, 0.125), (d, 0.125)],
+                        _ => vec![(x, 0.5), (y, 0.5)],
+                    };
+                    vertex_buffer.push(blend(vertices, &weights));
+                    vertex_buffer.len() - 1
+                })
+            });
+            for indexes in [(a, ab, ca), (b, bc, ab), (c, ca, bc), (ab, bc, ca)] {
+                index_buffer.push(Face {
+                    indexes,
+                    ..face.clone()
+                });
+            }
+        }
+
+        self.vertex_buffer = vertex_buffer;
+        self.index_buffer = index_buffer;
+        self.polygons.clear();
+        self.lods.clear();
+        if had_normals {
+            self.recompute_normals();
+        }
+    }
+
+    /// Refines the model with one step of Catmull-Clark subdivision, which splits every polygon
+    /// into quads and moves the vertices to approach a smooth surface. Models without
+    /// [`Model::polygons`] are subdivided from their triangles.
+    ///
+    /// The resulting quads are kept in [`Model::polygons`]. Vertex normals are recomputed if the
+    /// model had any.
+    pub fn subdivide_catmull_clark(&mut self) {
+        let had_normals = self.has_normals();
+        let polygons = self.polygons();
+        let vertex_count = self.vertex_buffer.len();
+
+        // Each polygon takes the colour of its first triangle, as the index buffer holds the
+        // fans of the polygons in order
+        let mut first_triangle = 0;
+        let face_colors: Vec<Option<Color>> = polygons
+            .iter()
+            .map(|polygon| {
+                let color = self.index_buffer.get(first_triangle).and_then(|f| f.color);
+                first_triangle += polygon.len() - 2;
+                color
+            })
+            .collect();
+
+        // Original vertices, followed by a point in the middle of each polygon
+        let mut points = self.vertex_buffer.clone();
+        for polygon in &polygons {
+            let weight = 1.0 / polygon.len() as f32;
+            let weights: Vec<(usize, f32)> = polygon.iter().map(|&v| (v, weight)).collect();
+            points.push(blend(&self.vertex_buffer, &weights));
+        }
+        let face_point = |face: usize| vertex_count + face;
+
+        let mut edge_faces: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        let mut vertex_faces = vec![vec![]; vertex_count];
+        for (face, polygon) in polygons.iter().enumerate() {
+            for (i, &vertex) in polygon.iter().enumerate() {
+                let next = polygon[(i + 1) % polygon.len()];
+                edge_faces.entry(edge(vertex, next)).or_default().push(face);
+                vertex_faces[vertex].push(face);
+            }
+        }
+        let (neighbours, boundary) = neighbours(vertex_count, &edge_faces);
+
+        let mut vertex_buffer: Vec<Vertex> = (0..vertex_count)
+            .map(|vertex| {
+                let (neighbours, faces) = (&neighbours[vertex], &vertex_faces[vertex]);
+                let valence = neighbours.len();
+                let weights = if boundary[vertex].len() == 2 {
+                    vec![
+                        (vertex, 0.75),
+                        (boundary[vertex][0], 0.125),
+                        (boundary[vertex][1], 0.125),
+                    ]
+                } else if boundary[vertex].is_empty() && valence >= 3 && !faces.is_empty() {
+                    // (F + 2R + (n - 3)P) / n, with R the average of the edge midpoints
+                    let n = valence as f32;
+                    let mut weights = vec![(vertex, (n - 2.0) / n)];
+                    weights.extend(neighbours.iter().map(|&other| (other, 1.0 / (n * n))));
+                    let face_weight = 1.0 / (n * faces.len() as f32);
+                    weights.extend(faces.iter().map(|&face| (face_point(face), face_weight)));
+                    weights
+                } else {
+                    vec![(vertex, 1.0)]
+                };
+                blend(&points, &weights)
+            })
+            .collect();
+        vertex_buffer.extend_from_slice(&points[vertex_count..]);
+
+        let mut edge_points: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut edge_point = |a: usize, b: usize| {
+            *edge_points.entry(edge(a, b)).or_insert_with(|| {
+                let weights = match edge_faces[&edge(a, b)].as_slice() {
+                    &[f, g] => vec![
+                        (a, 0.25),
+                        (b, 0.25),
+                        (face_point(f), 0.25),
+                        (face_point(g), 0.25),
+                    ],
+                    _ => vec![(a, 0.5), (b, 0.5)],
+                };
+                vertex_buffer.push(blend(&points, &weights));
+                vertex_buffer.len() - 1
+            })
+        };
+
+        let mut quads = Vec::with_capacity(polygons.iter().map(Vec::len).sum());
+        let mut quad_colors = Vec::with_capacity(quads.capacity());
+        for (face, polygon) in polygons.iter().enumerate() {
+            let len = polygon.len();
+            for i in 0..len {
+                let (previous, vertex, next) = (
+                    polygon[(i + len - 1) % len],
+                    polygon[i],
+                    polygon[(i + 1) % len],
+                );
+                quads.push(vec![
+                    vertex,
+                    edge_point(vertex, next),
+                    face_point(face),
+                    edge_point(previous, vertex),
+                ]);
+                quad_colors.push(face_colors[face]);
+            }
+        }
+
+        self.index_buffer = quads
+            .iter()
+            .zip(quad_colors)
+            .flat_map(|(quad, color)| triangulate(quad).map(move |face| Face { color, ..face }))
+            .collect();
+        self.vertex_buffer = vertex_buffer;
+        self.polygons = quads;
+        self.lods.clear();
+        if had_normals {
+            self.recompute_normals();
+        }
+    }
+
+    fn has_normals(&self) -> bool {
+        self.vertex_buffer
+            .iter()
+            .any(|vertex| vertex.normal.is_some())
+    }
+}
+
+/// Weights of the original vertices that make up a vertex after Loop subdivision.
+fn loop_weights(vertex: usize, neighbours: &[usize], boundary: &[usize]) -> Vec<(usize, f32)> {
+    if let &[b0, b1] = boundary {
+        return vec![(vertex, 0.75), (b0, 0.125), (b1, 0.125)];
+    }
+    if !boundary.is_empty() || neighbours.len() < 3 {
+        // Corners and other irregular vertices stay in place
+        return vec![(vertex, 1.0)];
+    }
+    let n = neighbours.len() as f32;
+    let cosine = 0.375 + 0.25 * (std::f32::consts::TAU / n).cos();
+    let beta = (0.625 - cosine * cosine) / n;
+    let mut weights = vec![(vertex, 1.0 - n * beta)];
+    weights.extend(neighbours.iter().map(|&other| (other, beta)));
+    weights
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::renderer::{model::Model, Vertex};
+    use nalgebra::Point3;
+
+    fn cube(polygons: Vec<Vec<usize>>) -> Model {
+        let vertices = (0..8)
+            .map(|i| {
+                let coordinate = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+                Vertex::new(Point3::new(coordinate(1), coordinate(2), coordinate(4)))
+            })
+            .collect();
+        Model::from_polygons(vertices, polygons).unwrap()
+    }
+
+    fn quad_cube() -> Model {
+        cube(vec![
+            vec![0, 2, 3, 1],
+            vec![4, 5, 7, 6],
+            vec![0, 1, 5, 4],
+            vec![2, 6, 7, 3],
+            vec![0, 4, 6, 2],
+            vec![1, 3, 7, 5],
+        ])
+    }
+
+    #[test]
+    fn test_midpoint_keeps_shape() {
+        let mut model = Model::from_polygons(
+            [(0.0, 0.0), (2.0, 0.0), (0.0, 2.0)]
+                .map(|(x, y)| Vertex::new(Point3::new(x, y, 0.0)))
+                .to_vec(),
+            vec![vec![0, 1, 2]],
+        )
+        .unwrap();
+        model.subdivide_midpoint();
+        assert_eq!(4, model.index_buffer.len());
+        assert_eq!(6, model.vertex_buffer.len());
+        assert!(model
+            .vertex_buffer
+            .iter()
+            .any(|v| v.position == Point3::new(1.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_loop_smooths_closed_mesh() {
+        let mut model = quad_cube();
+        model.polygons.clear();
+        model.subdivide_loop();
+        // 8 vertices and 18 edges
+        assert_eq!(26, model.vertex_buffer.len());
+        assert_eq!(48, model.index_buffer.len());
+        let corner = model.vertex_buffer[7].position;
+        assert!(corner.x < 1.0 && corner.y < 1.0 && corner.z < 1.0);
+    }
+
+    #[test]
+    fn test_catmull_clark_keeps_quads() {
+        let mut model = quad_cube();
+        model.subdivide_catmull_clark();
+        // 8 vertices, 6 faces and 12 edges
+        assert_eq!(26, model.vertex_buffer.len());
+        assert_eq!(24, model.polygons.len());
+        assert!(model.polygons.iter().all(|polygon| polygon.len() == 4));
+        assert_eq!(48, model.index_buffer.len());
+
+        let corner = model.vertex_buffer[7].position;
+        assert!((corner - Point3::new(5.0, 5.0, 5.0) / 9.0).norm() < 1e-6);
+    }
+}
@@ -0,0 +1,456 @@
+use std::{error::Error, fs, io::BufRead};
+
+use ::image::{DynamicImage, Rgb};
+use gltf::{buffer, image, mesh::Mode, Document, Gltf};
+use nalgebra::{Matrix3, Matrix4, Point2, Point3, Vector3};
+
+use super::{
+    material::Material,
+    model::{FileResolver, Model, Resolver},
+    Color, CullMode, Face, Vertex,
+};
+
+/// An image from a scene file, as sRGB colours in rows from the top.
+#[derive(Clone, Debug)]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Color>,
+}
+
+impl Texture {
+    /// Colour of the nearest pixel at the texture coordinates, repeating the texture outside of
+    /// 0 to 1.
+    pub fn sample(&self, u: f32, v: f32) -> Color {
+        let wrap = |coordinate: f32, size: u32| {
+            let texel = (coordinate.rem_euclid(1.0) * size as f32) as u32;
+            texel.min(size - 1) as usize
+        };
+        let (x, y) = (wrap(u, self.width), wrap(v, self.height));
+        self.pixels[y * self.width as usize + x]
+    }
+
+    /// Converts a decoded image, dropping its alpha channel.
+    fn from_image(image: &DynamicImage) -> Texture {
+        let image = image.to_rgb8();
+        Texture {
+            width: image.width(),
+            height: image.height(),
+            pixels: image
+                .pixels()
+                .map(|&Rgb([r, g, b])| Color(r, g, b))
+                .collect(),
+        }
+    }
+}
+
+/// A node of a scene's hierarchy.
+#[derive(Clone, Debug)]
+pub struct SceneNode {
+    pub name: Option<String>,
+    /// Transform relative to the parent node, which may include scaling.
+    pub transform: Matrix4<f32>,
+    /// Indexes of the child nodes in [`Scene::nodes`].
+    pub children: Vec<usize>,
+    /// One model for each part of the node's mesh, in the space of the node.
+    pub meshes: Vec<Model>,
+}
+
+/// A hierarchy of meshes imported from a glTF file. Only available with the `gltf` feature.
+#[derive(Clone, Debug)]
+pub struct Scene {
+    pub nodes: Vec<SceneNode>,
+    /// Indexes of the nodes at the top of the hierarchy.
+    pub roots: Vec<usize>,
+    /// Images of the file, in the order they are listed in it.
+    pub textures: Vec<Texture>,
+}
+
+impl Scene {
+    /// Loads a `.gltf` file, with its buffers embedded or in files next to it, or a `.glb` file.
+    /// Only files on the local disk are read. See [`Scene::from_gltf`].
+    pub fn load_gltf(filename: &str) -> Result<Scene, Box<dyn Error>> {
+        let data = fs::read(filename)?;
+        Scene::from_gltf(&data, &FileResolver::for_file(filename))
+    }
+
+    /// Loads a `.gltf` or `.glb` file from everything the reader returns. See
+    /// [`Scene::from_gltf`].
+    pub fn from_gltf_reader(
+        mut reader: impl BufRead,
+        resolver: &dyn Resolver,
+    ) -> Result<Scene, Box<dyn Error>> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        Scene::from_gltf(&data, resolver)
+    }
+
+    /// Parses a `.gltf` or `.glb` file from memory. Buffers and images in other files are read
+    /// through the resolver, embedded ones are decoded directly. URIs with any other scheme than
+    /// `data:` and `file:` are rejected.
+    ///
+    /// Each part of a mesh becomes a [`Model`]. Its material takes the base colour factor and
+    /// alpha of the glTF material, and the base colour texture is sampled into the vertex
+    /// colours. Double sided materials disable culling. Parts that are not made of triangles
+    /// are skipped.
+    pub fn from_gltf(data: &[u8], resolver: &dyn Resolver) -> Result<Scene, Box<dyn Error>> {
+        let Gltf { document, mut blob } = Gltf::from_slice(data)?;
+
+        let mut buffers = vec![];
+        for buffer in document.buffers() {
+            let data = match buffer.source() {
+                buffer::Source::Bin => blob.take().ok_or("missing binary chunk")?,
+                buffer::Source::Uri(uri) => read_uri(uri, resolver)?,
+            };
+            if data.len() < buffer.length() {
+                return Err(format!("buffer {} is too short", buffer.index()).into());
+            }
+            buffers.push(buffer::Data(data));
+        }
+
+        let mut textures = vec![];
+        for image in document.images() {
+            let encoded = match image.source() {
+                image::Source::View { view, .. } => {
+                    let start = view.offset();
+                    buffers
+                        .get(view.buffer().index())
+                        .zip(start.checked_add(view.length()))
+                        .and_then(|(buffer, end)| buffer.get(start..end))
+                        .ok_or_else(|| format!("image {} is outside of its buffer", image.index()))?
+                        .to_vec()
+                }
+                image::Source::Uri { uri, .. } => read_uri(uri, resolver)?,
+            };
+            textures.push(Texture::from_image(&::image::load_from_memory(&encoded)?));
+        }
+
+        let mut nodes = Vec::with_capacity(document.nodes().len());
+        for node in document.nodes() {
+            let mut meshes = vec![];
+            for primitive in node.mesh().iter().flat_map(|mesh| mesh.primitives()) {
+                if primitive.mode() != Mode::Triangles {
+                    continue;
+                }
+                meshes.push(primitive_model(&primitive, &buffers, &textures)?);
+            }
+            nodes.push(SceneNode {
+                name: node.name().map(str::to_string),
+                transform: Matrix4::from(node.transform().matrix()),
+                children: node.children().map(|child| child.index()).collect(),
+                meshes,
+            });
+        }
+
+        Ok(Scene {
+            nodes,
+            roots: root_nodes(&document),
+            textures,
+        })
+    }
+
+    /// Transform of every node relative to the scene, with the transforms of its parents applied.
+    ///
+    /// Each node is placed under the first parent it is reached from, so nodes that are their own
+    /// ancestors do not loop. Indexes of missing nodes are skipped.
+    pub fn world_transforms(&self) -> Vec<Matrix4<f32>> {
+        let mut transforms = vec![Matrix4::identity(); self.nodes.len()];
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack: Vec<(usize, Matrix4<f32>)> = self
+            .roots
+            .iter()
+            .map(|&root| (root, Matrix4::identity()))
+            .collect();
+        while let Some((index, parent)) = stack.pop() {
+            let Some(node) = self.nodes.get(index) else {
+                continue;
+            };
+            if std::mem::replace(&mut visited[index], true) {
+                continue;
+            }
+            transforms[index] = parent * node.transform;
+            stack.extend(
+                node.children
+                    .iter()
+                    .map(|&child| (child, transforms[index])),
+            );
+        }
+        transforms
+    }
+
+    /// All meshes of the scene, with the transforms of their nodes applied to the vertices, so
+    /// that they can be drawn as they are.
+    pub fn models(&self) -> Vec<Model> {
+        let transforms = self.world_transforms();
+        let mut models = vec![];
+        for (node, transform) in self.nodes.iter().zip(transforms) {
+            let linear: Matrix3<f32> = transform.fixed_view::<3, 3>(0, 0).into();
+            let normal_matrix = linear
+                .try_inverse()
+                .map_or(linear, |inverse| inverse.transpose());
+            for mesh in &node.meshes {
+                let mut model = mesh.clone();
+                for vertex in &mut model.vertex_buffer {
+                    vertex.position = transform.transform_point(&vertex.position);
+                    vertex.normal = vertex
+                        .normal
+                        .and_then(|normal| (normal_matrix * normal).try_normalize(f32::EPSILON));
+                }
+                // Mirroring turns the faces inside out, the normals are already right
+                if linear.determinant() < 0.0 {
+                    let normals: Vec<_> = model.vertex_buffer.iter().map(|v| v.normal).collect();
+                    model.flip_winding();
+                    for (vertex, normal) in model.vertex_buffer.iter_mut().zip(normals) {
+                        vertex.normal = normal;
+                    }
+                }
+                models.push(model);
+            }
+        }
+        models
+    }
+}
+
+/// Reads the data a URI in a glTF file refers to.
+fn read_uri(uri: &str, resolver: &dyn Resolver) -> Result<Vec<u8>, Box<dyn Error>> {
+    if uri.starts_with("data:") {
+        return Ok(buffer::Data::from_source(buffer::Source::Uri(uri), None)?.0);
+    }
+    let path = uri
+        .strip_prefix("file://")
+        .or_else(|| uri.strip_prefix("file:"));
+    if path.is_none() && uri.contains(':') {
+        return Err(format!("unsupported URI {uri:?}, only local files can be read").into());
+    }
+    Ok(resolver.resolve(&percent_decode(path.unwrap_or(uri)))?)
+}
+
+/// Decodes the `%XX` escapes of a URI.
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Nodes of the default scene, or all nodes without a parent if there is none.
+fn root_nodes(document: &Document) -> Vec<usize> {
+    if let Some(scene) = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        return scene.nodes().map(|node| node.index()).collect();
+    }
+    let mut is_child = vec![false; document.nodes().len()];
+    for child in document.nodes().flat_map(|node| node.children()) {
+        is_child[child.index()] = true;
+    }
+    (0..is_child.len())
+        .filter(|&index| !is_child[index])
+        .collect()
+}
+
+fn primitive_model(
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data],
+    textures: &[Texture],
+) -> Result<Model, Box<dyn Error>> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+    let positions = reader
+        .read_positions()
+        .ok_or("mesh primitive has no positions")?;
+    let mut vertex_buffer: Vec<Vertex> = positions
+        .map(|position| Vertex::new(Point3::from(position)))
+        .collect();
+
+    if let Some(normals) = reader.read_normals() {
+        for (vertex, normal) in vertex_buffer.iter_mut().zip(normals) {
+            vertex.normal = Some(Vector3::from(normal));
+        }
+    }
+    // Vertex colours in glTF are in linear light, and their alpha only counts in blend mode
+    if let Some(colors) = reader.read_colors(0) {
+        let blend = matches!(
+            primitive.material().alpha_mode(),
+            gltf::material::AlphaMode::Blend
+        );
+        for (vertex, [r, g, b, a]) in vertex_buffer.iter_mut().zip(colors.into_rgba_f32()) {
+            vertex.color = Some(Color::from_linear(Vector3::new(r, g, b)));
+            vertex.alpha = blend.then_some(a);
+        }
+    }
+
+    if let Some(coordinates) = reader.read_tex_coords(0) {
+        for (vertex, [u, v]) in vertex_buffer.iter_mut().zip(coordinates.into_f32()) {
+            vertex.uv = Some(Point2::new(u, v));
+        }
+    }
+
+    let gltf_material = primitive.material();
+    let pbr = gltf_material.pbr_metallic_roughness();
+    if let Some(info) = pbr.base_color_texture() {
+        let texture = textures.get(info.texture().source().index());
+        if let (Some(texture), Some(coordinates)) =
+            (texture, reader.read_tex_coords(info.tex_coord()))
+        {
+            for (vertex, [u, v]) in vertex_buffer.iter_mut().zip(coordinates.into_f32()) {
+                let color = texture.sample(u, v);
+                vertex.color = Some(vertex.color.map_or(color, |tint| tint * color));
+            }
+        }
+    }
+
+    let index_buffer: Vec<Face> = match reader.read_indices() {
+        Some(indices) => indices
+            .into_u32()
+            .map(|index| index as usize)
+            .collect::<Vec<_>>()
+            .chunks_exact(3)
+            .map(|face| Face::new(face[0], face[1], face[2]))
+            .collect(),
+        None => (0..vertex_buffer.len() / 3)
+            .map(|face| Face::new(face * 3, face * 3 + 1, face * 3 + 2))
+            .collect(),
+    };
+
+    let mut model = Model::from_buffers(vertex_buffer, index_buffer)?;
+    let [r, g, b, a] = pbr.base_color_factor();
+    model.material = Some(Material {
+        color: Color::from_linear(Vector3::new(r, g, b)),
+        alpha: match gltf_material.alpha_mode() {
+            gltf::material::AlphaMode::Blend => a,
+            _ => 1.0,
+        },
+        ..Material::default()
+    });
+    if gltf_material.double_sided() {
+        model.cull_mode = Some(CullMode::None);
+    }
+    Ok(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scene, SceneNode};
+    use crate::renderer::model::NoResolver;
+    use nalgebra::{Matrix4, Point3, Vector3};
+    use std::{fs, io};
+
+    /// A triangle in a child node, with its buffer at the URI.
+    fn triangle_gltf(uri: &str) -> String {
+        r#"{
+            "asset": {"version": "2.0"},
+            "scene": 0,
+            "scenes": [{"nodes": [0]}],
+            "nodes": [
+                {"name": "parent", "translation": [0, 0, -5], "children": [1]},
+                {"name": "child", "translation": [1, 0, 0], "scale": [2, 2, 2], "mesh": 0}
+            ],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "material": 0}]}],
+            "materials": [{
+                "pbrMetallicRoughness": {"baseColorFactor": [1, 0, 0, 0.5]},
+                "alphaMode": "BLEND"
+            }],
+            "accessors": [{
+                "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0, 0, 0], "max": [1, 1, 0]
+            }],
+            "bufferViews": [{"buffer": 0, "byteLength": 36}],
+            "buffers": [{"uri": "URI", "byteLength": 36}]
+        }"#
+        .replace("URI", uri)
+    }
+
+    fn triangle_buffer() -> Vec<u8> {
+        let positions: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        positions.iter().flat_map(|p| p.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_load_gltf_with_adjacent_buffer() {
+        let directory = std::env::temp_dir().join(format!("bren-gltf-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("triangle.bin"), triangle_buffer()).unwrap();
+        let path = directory.join("triangle.gltf");
+        fs::write(&path, triangle_gltf("triangle.bin")).unwrap();
+
+        let scene = Scene::load_gltf(path.to_str().unwrap()).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(vec![0], scene.roots);
+        assert_eq!(Some("child".to_string()), scene.nodes[1].name);
+        let models = scene.models();
+        assert_eq!(1, models.len());
+        assert_eq!(1, models[0].index_buffer.len());
+        assert_eq!(
+            Point3::new(3.0, 0.0, -5.0),
+            models[0].vertex_buffer[1].position
+        );
+        let material = models[0].material.unwrap();
+        assert_eq!(255, material.color.0);
+        assert_eq!(0.5, material.alpha);
+    }
+
+    #[test]
+    fn test_gltf_from_memory_uses_resolver() {
+        let gltf = triangle_gltf("my%20triangle.bin");
+        let resolver = |path: &str| match path {
+            "my triangle.bin" => Ok(triangle_buffer()),
+            _ => Err(io::Error::from(io::ErrorKind::NotFound)),
+        };
+        let scene = Scene::from_gltf_reader(gltf.as_bytes(), &resolver).unwrap();
+        assert_eq!(1, scene.models().len());
+
+        assert!(Scene::from_gltf(gltf.as_bytes(), &NoResolver).is_err());
+        let remote = triangle_gltf("https://example.com/triangle.bin");
+        assert!(Scene::from_gltf(remote.as_bytes(), &resolver).is_err());
+    }
+
+    #[test]
+    fn test_cyclic_nodes() {
+        let node = |children| SceneNode {
+            name: None,
+            transform: Matrix4::new_translation(&Vector3::new(1.0, 0.0, 0.0)),
+            children,
+            meshes: vec![],
+        };
+        let scene = Scene {
+            nodes: vec![node(vec![1]), node(vec![0, 2])],
+            roots: vec![0],
+            textures: vec![],
+        };
+        let transforms = scene.world_transforms();
+        assert_eq!(2.0, transforms[1][(0, 3)]);
+    }
+
+    #[test]
+    fn test_image_outside_of_its_buffer() {
+        let gltf = triangle_gltf("triangle.bin").replace(
+            r#""bufferViews": [{"buffer": 0, "byteLength": 36}],"#,
+            r#""bufferViews": [
+                {"buffer": 0, "byteLength": 36},
+                {"buffer": 0, "byteOffset": 32, "byteLength": 100}
+            ],
+            "images": [{"bufferView": 1, "mimeType": "image/png"}],"#,
+        );
+        let resolver = |_: &str| Ok(triangle_buffer());
+        let error = Scene::from_gltf(gltf.as_bytes(), &resolver).unwrap_err();
+        assert_eq!("image 0 is outside of its buffer", error.to_string());
+    }
+}
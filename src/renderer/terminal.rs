@@ -0,0 +1,116 @@
+use crossterm::{
+    cursor,
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+    },
+    execute, terminal,
+};
+use std::{
+    io::{self, stdout},
+    panic,
+    sync::{Mutex, Once, PoisonError},
+    time::Duration,
+};
+
+/// Options of the alive [`TerminalGuard`], if there is one.
+static ACTIVE: Mutex<Option<TerminalOptions>> = Mutex::new(None);
+
+static PANIC_HOOK: Once = Once::new();
+
+/// What a [`TerminalGuard`] sets up besides the alternate screen and hiding the cursor.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TerminalOptions {
+    /// Enables raw mode, where keys are read one at a time without being printed. Key presses
+    /// such as Ctrl-C no longer end the program, so it should read them as events instead.
+    pub raw_mode: bool,
+    /// Reports mouse clicks, drags and scrolling as events.
+    pub mouse_capture: bool,
+}
+
+/// Prepares the terminal for drawing, and puts it back the way it was when dropped.
+///
+/// Output goes to the alternate screen, leaving the shell's scrollback untouched, and the cursor
+/// is hidden. The terminal is also restored when the program panics, before the panic message is
+/// printed. Only one guard can be alive at a time.
+///
+/// Without raw mode, ending the program with Ctrl-C skips the restoring. With it, programs can
+/// wait for the user to quit with [`quit_requested`].
+#[derive(Debug)]
+pub struct TerminalGuard {
+    _private: (),
+}
+
+impl TerminalGuard {
+    /// Sets up the terminal with the default [`TerminalOptions`].
+    pub fn new() -> io::Result<TerminalGuard> {
+        TerminalGuard::with_options(TerminalOptions::default())
+    }
+
+    /// Sets up the terminal with the options. Fails if another guard is alive.
+    pub fn with_options(options: TerminalOptions) -> io::Result<TerminalGuard> {
+        {
+            let mut active = ACTIVE.lock().unwrap_or_else(PoisonError::into_inner);
+            if active.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "the terminal is already set up by another guard",
+                ));
+            }
+            *active = Some(options);
+        }
+        PANIC_HOOK.call_once(|| {
+            let previous = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                restore();
+                previous(info);
+            }));
+        });
+
+        let guard = TerminalGuard { _private: () };
+        if options.raw_mode {
+            terminal::enable_raw_mode()?;
+        }
+        execute!(stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        if options.mouse_capture {
+            execute!(stdout(), EnableMouseCapture)?;
+        }
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// Waits up to `timeout` for input, returning whether the user pressed `q`, Esc or Ctrl-C.
+/// Other input is discarded.
+pub fn quit_requested(timeout: Duration) -> io::Result<bool> {
+    if !event::poll(timeout)? {
+        return Ok(false);
+    }
+    Ok(match event::read()? {
+        Event::Key(key) if key.kind != KeyEventKind::Release => match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => true,
+            KeyCode::Char('c') => key.modifiers.contains(KeyModifiers::CONTROL),
+            _ => false,
+        },
+        _ => false,
+    })
+}
+
+/// Undoes what the alive guard has set up, if there is one. Errors are ignored, as the terminal
+/// should be restored as well as it can be.
+fn restore() {
+    let Some(options) = ACTIVE.lock().unwrap_or_else(PoisonError::into_inner).take() else {
+        return;
+    };
+    if options.mouse_capture {
+        let _ = execute!(stdout(), DisableMouseCapture);
+    }
+    let _ = execute!(stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+    if options.raw_mode {
+        let _ = terminal::disable_raw_mode();
+    }
+}
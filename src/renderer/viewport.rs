@@ -1,12 +1,35 @@
 use crossterm::{
     cursor,
-    style::{Print, SetForegroundColor},
+    style::{
+        Attribute, Attributes, Print, ResetColor, SetAttribute, SetAttributes, SetBackgroundColor,
+        SetForegroundColor,
+    },
     terminal, QueueableCommand,
 };
 use std::io::{self, stdout, Stdout, Write};
 
 use super::Color;
 
+/// Colours and attributes of overlay text.
+///
+/// Colours left as `None` use the terminal's defaults.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TextStyle {
+    /// Colour of the characters.
+    pub fg: Option<Color>,
+    /// Colour of the cells behind the characters.
+    pub bg: Option<Color>,
+    /// Attributes such as bold or underlined, see [`crossterm::style::Attribute`].
+    pub attributes: Attributes,
+}
+
+/// A single character of overlay text.
+#[derive(Clone, Copy, Debug)]
+struct TextCell {
+    character: char,
+    style: TextStyle,
+}
+
 /// Struct that keeps track of the drawable screen area.
 ///
 /// Applications should manage terminal resizes manually.
@@ -15,6 +38,8 @@ pub struct Viewport {
     screen_out: Stdout,
     size: (u16, u16),
     origin: (u16, u16),
+    /// Text drawn over the braille characters, one entry per terminal cell in row-major order.
+    text_overlay: Vec<Option<TextCell>>,
 }
 
 impl Default for Viewport {
@@ -34,6 +59,7 @@ impl Viewport {
             screen_out: stdout(),
             size: (width, height),
             origin: (0, 0),
+            text_overlay: vec![None; term_size.0 as usize * term_size.1 as usize],
         }
     }
 
@@ -46,6 +72,7 @@ impl Viewport {
             screen_out: stdout(),
             size: (width, height),
             origin: (x0, y0),
+            text_overlay: vec![None; w as usize * h as usize],
         }
     }
 
@@ -60,9 +87,60 @@ impl Viewport {
         self.size
     }
 
+    /// Size of the viewport in terminal cells, as columns and rows.
+    pub fn size_in_cells(&self) -> (u16, u16) {
+        (self.size.0 / 2, self.size.1 / 4)
+    }
+
+    /// Writes text over the braille output, starting at the given column and row of the
+    /// viewport. Rows count down from the top, and each character takes up one cell. Text that
+    /// does not fit in the viewport is cut off.
+    ///
+    /// Only characters known to be one cell wide are drawn: ASCII, Latin, Greek and Cyrillic
+    /// letters, box drawing and braille. Others, such as control characters, combining marks,
+    /// emoji and CJK, are replaced with `?`, so that they cannot shift the rest of the row.
+    ///
+    /// The text stays until [`clear_text`] is called.
+    ///
+    /// [`clear_text`]: #method.clear_text
+    pub fn draw_text(&mut self, column: u16, row: u16, text: &str, style: TextStyle) {
+        let (columns, rows) = self.size_in_cells();
+        if row >= rows {
+            return;
+        }
+        for (i, character) in text.chars().enumerate() {
+            let column = column as usize + i;
+            if column >= columns as usize {
+                break;
+            }
+            let character = if is_single_width(character) {
+                character
+            } else {
+                '?'
+            };
+            self.text_overlay[row as usize * columns as usize + column] =
+                Some(TextCell { character, style });
+        }
+    }
+
+    /// Removes all overlay text.
+    pub fn clear_text(&mut self) {
+        self.text_overlay.fill(None);
+    }
+
     /// Takes the screen buffer, converts to braille characters and outputs the result to the
-    /// viewport.
-    pub fn draw_chars(&mut self, v: &[Vec<Color>]) {
+    /// viewport. Overlay text is printed in place of the braille characters it covers.
+    ///
+    /// Dots are lit where `coverage` is set, and characters take the average colour of their lit
+    /// dots. The cells are filled with `background`, or left with the terminal's own background
+    /// colour when it is `None`.
+    pub fn draw_chars(
+        &mut self,
+        v: &[Vec<Color>],
+        coverage: &[Vec<bool>],
+        background: Option<Color>,
+    ) {
+        let background = background.map_or(crossterm::style::Color::Reset, Color::into);
         self.screen_out
             .queue(cursor::MoveTo(self.origin.0, self.origin.1))
             .unwrap();
@@ -71,61 +149,88 @@ impl Viewport {
                 .queue(cursor::MoveTo(self.origin.0, i as u16 + self.origin.1))
                 .unwrap();
             for col in (0..v.len()).step_by(2) {
-                let tile: [[Color; 4]; 2] = [
-                    [
-                        v[col][row],
-                        v[col][row - 1],
-                        v[col][row - 2],
-                        v[col][row - 3],
-                    ],
-                    [
-                        v[col + 1][row],
-                        v[col + 1][row - 1],
-                        v[col + 1][row - 2],
-                        v[col + 1][row - 3],
-                    ],
-                ];
+                let cell = i * (v.len() / 2) + col / 2;
+                if let Some(text) = self.text_overlay[cell] {
+                    Self::print_text_cell(&mut self.screen_out, text);
+                    continue;
+                }
+                let tile = Self::tile(v, col, row);
+                let coverage_tile = Self::tile(coverage, col, row);
                 self.screen_out
-                    .queue(SetForegroundColor(Self::average_color(tile)))
+                    .queue(SetBackgroundColor(background))
+                    .unwrap()
+                    .queue(SetForegroundColor(Self::average_color(tile, coverage_tile)))
                     .unwrap()
-                    .queue(Print(Self::into_braille(tile)))
+                    .queue(Print(Self::into_braille(coverage_tile)))
                     .unwrap();
             }
         }
+        self.screen_out.queue(ResetColor).unwrap();
         self.screen_out.flush().unwrap();
     }
 
-    fn average_color(tile: [[Color; 4]; 2]) -> crossterm::style::Color {
-        let mut sum_r: u32 = 0;
-        let mut sum_g: u32 = 0;
-        let mut sum_b: u32 = 0;
-        for dot in tile.into_iter().flatten() {
-            sum_r += dot.0 as u32;
-            sum_g += dot.1 as u32;
-            sum_b += dot.2 as u32;
-        }
-        crossterm::style::Color::Rgb {
-            r: (sum_r / 8) as u8,
-            g: (sum_g / 8) as u8,
-            b: (sum_b / 8) as u8,
-        }
+    /// Takes the 2 by 4 slice of a buffer that makes up the character with `col` as its left
+    /// column and `row` as its top row.
+    fn tile<T: Copy>(v: &[Vec<T>], col: usize, row: usize) -> [[T; 4]; 2] {
+        [
+            [
+                v[col][row],
+                v[col][row - 1],
+                v[col][row - 2],
+                v[col][row - 3],
+            ],
+            [
+                v[col + 1][row],
+                v[col + 1][row - 1],
+                v[col + 1][row - 2],
+                v[col + 1][row - 3],
+            ],
+        ]
+    }
+
+    fn print_text_cell(screen_out: &mut Stdout, text: TextCell) {
+        let fg = text
+            .style
+            .fg
+            .map_or(crossterm::style::Color::Reset, Color::into);
+        let bg = text
+            .style
+            .bg
+            .map_or(crossterm::style::Color::Reset, Color::into);
+        screen_out
+            .queue(SetForegroundColor(fg))
+            .unwrap()
+            .queue(SetBackgroundColor(bg))
+            .unwrap()
+            .queue(SetAttributes(text.style.attributes))
+            .unwrap()
+            .queue(Print(text.character))
+            .unwrap()
+            .queue(SetAttribute(Attribute::Reset))
+            .unwrap();
+    }
+
+    /// Average colour of the lit dots in the tile.
+    fn average_color(tile: [[Color; 4]; 2], coverage: [[bool; 4]; 2]) -> crossterm::style::Color {
+        let lit = tile
+            .into_iter()
+            .flatten()
+            .zip(coverage.into_iter().flatten())
+            .filter_map(|(dot, covered)| covered.then_some(dot));
+        Color::mean(lit).unwrap_or(Color::BLACK).into()
     }
 
-    /// Takes a 2 by 4 slice of the pixel buffer and converts it to a unicode braille character.
+    /// Takes a 2 by 4 slice of the coverage buffer and converts it to a unicode braille character.
     /// https://en.wikipedia.org/wiki/Braille_Patterns#Identifying.2C_naming_and_ordering
-    fn into_braille(tile: [[Color; 4]; 2]) -> char {
-        let ordered_dots: [Color; 8] = [
+    fn into_braille(tile: [[bool; 4]; 2]) -> char {
+        let ordered_dots: [bool; 8] = [
             tile[0][0], tile[0][1], tile[0][2], tile[1][0], tile[1][1], tile[1][2], tile[0][3],
             tile[1][3],
         ];
 
         let mut pattern: u32 = 0;
         for (i, dot) in ordered_dots.into_iter().enumerate() {
-            let dot: u32 = if dot.0 != 0 || dot.1 != 0 || dot.2 != 0 {
-                1
-            } else {
-                0
-            };
+            let dot: u32 = if dot { 1 } else { 0 };
             pattern += dot << i;
         }
 
@@ -134,31 +239,100 @@ impl Viewport {
     }
 }
 
+/// Whether the character surely takes up exactly one terminal cell. Ranges with combining marks
+/// are left out.
+fn is_single_width(character: char) -> bool {
+    matches!(
+        character,
+        ' '..='~'
+            | '\u{a1}'..='\u{ac}'
+            | '\u{ae}'..='\u{2ff}'
+            | '\u{370}'..='\u{482}'
+            | '\u{48a}'..='\u{52f}'
+            | '\u{2500}'..='\u{259f}'
+            | '\u{2800}'..='\u{28ff}'
+    )
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::renderer::Color;
+    use crate::renderer::{Color, Renderer};
+    use nalgebra::Point3;
+
+    use super::{TextStyle, Viewport};
+
+    #[test]
+    fn test_average_color_ignores_unlit() {
+        let black = Color::BLACK;
+        let tile = [
+            [Color::RED, black, black, black],
+            [Color::BLUE, black, black, black],
+        ];
+        let coverage = [[true, false, false, false], [true, false, false, false]];
+        assert_eq!(
+            crossterm::style::Color::Rgb {
+                r: 188,
+                g: 0,
+                b: 188
+            },
+            Viewport::average_color(tile, coverage)
+        );
+    }
+
+    #[test]
+    fn test_draw_text_clips() {
+        let mut viewport = Viewport::with_size_and_pos(4, 2, 0, 0);
+        viewport.draw_text(2, 1, "abc", TextStyle::default());
+        viewport.draw_text(0, 2, "hidden", TextStyle::default());
+
+        let characters: String = viewport
+            .text_overlay
+            .iter()
+            .map(|cell| cell.map_or('.', |cell| cell.character))
+            .collect();
+        assert_eq!("......ab", characters);
+
+        viewport.clear_text();
+        assert!(viewport.text_overlay.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn test_draw_text_replaces_wide_characters() {
+        let mut viewport = Viewport::with_size_and_pos(8, 1, 0, 0);
+        viewport.draw_text(0, 0, "é猫\te\u{301}─🙂", TextStyle::default());
+
+        let characters: String = viewport
+            .text_overlay
+            .iter()
+            .map(|cell| cell.map_or('.', |cell| cell.character))
+            .collect();
+        assert_eq!("é??e?─?.", characters);
+    }
+
+    #[test]
+    fn test_label_follows_projection() {
+        let mut renderer = Renderer::new(Viewport::with_size_and_pos(10, 5, 0, 0));
+        renderer.draw_label(Point3::new(0.0, 0.0, -5.0), "x", TextStyle::default());
+        renderer.draw_label(Point3::new(0.0, 0.0, 5.0), "behind", TextStyle::default());
+
+        let labelled: Vec<(usize, char)> = renderer
+            .viewport
+            .text_overlay
+            .iter()
+            .enumerate()
+            .filter_map(|(i, cell)| cell.map(|cell| (i, cell.character)))
+            .collect();
+        // Center of a 10 by 5 cell viewport
+        assert_eq!(vec![(2 * 10 + 5, 'x')], labelled);
+    }
 
-    use super::Viewport;
     #[test]
     fn test_into_braille_all() {
         // o o
         // o o
         // o o
         // o o
-        let all_dots = [
-            [
-                Color(255, 255, 255),
-                Color(255, 255, 255),
-                Color(255, 255, 255),
-                Color(255, 255, 255),
-            ],
-            [
-                Color(255, 255, 255),
-                Color(255, 255, 255),
-                Color(255, 255, 255),
-                Color(255, 255, 255),
-            ],
-        ];
+        let all_dots = [[true, true, true, true], [true, true, true, true]];
         assert_eq!('\u{28FF}', Viewport::into_braille(all_dots));
     }
 
@@ -168,20 +342,7 @@ mod tests {
         // _ _
         // _ o
         // o _
-        let some_dots = [
-            [
-                Color(255, 255, 255),
-                Color(0, 0, 0),
-                Color(0, 0, 0),
-                Color(255, 255, 255),
-            ],
-            [
-                Color(255, 255, 255),
-                Color(0, 0, 0),
-                Color(255, 255, 255),
-                Color(0, 0, 0),
-            ],
-        ];
+        let some_dots = [[true, false, false, true], [true, false, true, false]];
         assert_eq!('\u{2869}', Viewport::into_braille(some_dots));
     }
 
@@ -191,20 +352,7 @@ mod tests {
         // _ _
         // _ _
         // _ _
-        let no_dots = [
-            [
-                Color(0, 0, 0),
-                Color(0, 0, 0),
-                Color(0, 0, 0),
-                Color(0, 0, 0),
-            ],
-            [
-                Color(0, 0, 0),
-                Color(0, 0, 0),
-                Color(0, 0, 0),
-                Color(0, 0, 0),
-            ],
-        ];
+        let no_dots = [[false, false, false, false], [false, false, false, false]];
         assert_eq!('\u{2800}', Viewport::into_braille(no_dots));
     }
 }